@@ -6,7 +6,8 @@ use spark_gap::gpu_context::GpuContext;
 use spark_gap::material::MATERIAL_BIND_GROUP_LAYOUT;
 use spark_gap::model::Model;
 use spark_gap::model_builder::MODEL_BIND_GROUP_LAYOUT;
-use spark_gap::model_mesh::ModelVertex;
+use spark_gap::model_mesh::{ModelVertex, MESH_BIND_GROUP_LAYOUT};
+use spark_gap::skinning::{skinning_wgsl, SkinningMethod};
 use spark_gap::texture_config::TextureType;
 
 use crate::run_loop::BACKGROUND_COLOR;
@@ -18,9 +19,9 @@ pub struct AnimRenderPass {
 }
 
 impl AnimRenderPass {
-    pub fn new(context: &GpuContext) -> Self {
-        let render_pipeline = create_render_pipeline(context);
-        let render_pipeline_2 = create_render_pipeline_2(context);
+    pub fn new(context: &GpuContext, skinning_method: SkinningMethod) -> Self {
+        let render_pipeline = create_render_pipeline(context, skinning_method);
+        let render_pipeline_2 = create_render_pipeline_2(context, skinning_method);
 
         Self {
             render_pipeline,
@@ -29,12 +30,9 @@ impl AnimRenderPass {
     }
 
     pub fn render(&self, context: &GpuContext, world: &World) {
-        let frame = context
-            .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
+        let frame = context.get_current_frame();
 
-        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = frame.create_view();
 
         let pass_description = wgpu::RenderPassDescriptor {
             label: Some("render pass"),
@@ -137,6 +135,7 @@ fn render_model<'a>(
         let material_bind_group = model.get_material_bind_group(&mesh, TextureType::Diffuse);
 
         render_pass.set_bind_group(2, material_bind_group, &[]);
+        render_pass.set_bind_group(3, &mesh.mesh_bind_group, &[]);
         render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         render_pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint32);
         render_pass.draw_indexed(0..mesh.num_elements, 0, 0..1);
@@ -144,24 +143,29 @@ fn render_model<'a>(
     render_pass
 }
 
-pub fn create_render_pipeline(context: &GpuContext) -> RenderPipeline {
+pub fn create_render_pipeline(context: &GpuContext, skinning_method: SkinningMethod) -> RenderPipeline {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let model_bind_group_layout = context.bind_layout_cache.get(MODEL_BIND_GROUP_LAYOUT).unwrap();
     let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
+    let mesh_bind_group_layout = context.bind_layout_cache.get(MESH_BIND_GROUP_LAYOUT).unwrap();
 
     let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[camera_bind_group_layout, model_bind_group_layout, material_bind_group_layout],
+        bind_group_layouts: &[
+            camera_bind_group_layout,
+            model_bind_group_layout,
+            material_bind_group_layout,
+            mesh_bind_group_layout,
+        ],
         push_constant_ranges: &[],
     });
 
     let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("animation_shader.wgsl"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("animation_shader.wgsl").into()),
+        source: wgpu::ShaderSource::Wgsl(shader_source(context, skinning_method, include_str!("animation_shader.wgsl")).into()),
     });
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
+    let swapchain_format = context.config.format;
 
     let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
@@ -199,24 +203,29 @@ pub fn create_render_pipeline(context: &GpuContext) -> RenderPipeline {
     render_pipeline
 }
 
-pub fn create_render_pipeline_2(context: &GpuContext) -> RenderPipeline {
+pub fn create_render_pipeline_2(context: &GpuContext, skinning_method: SkinningMethod) -> RenderPipeline {
     let camera_bind_group_layout = context.bind_layout_cache.get(CAMERA_BIND_GROUP_LAYOUT).unwrap();
     let model_bind_group_layout = context.bind_layout_cache.get(MODEL_BIND_GROUP_LAYOUT).unwrap();
     let material_bind_group_layout = context.bind_layout_cache.get(MATERIAL_BIND_GROUP_LAYOUT).unwrap();
+    let mesh_bind_group_layout = context.bind_layout_cache.get(MESH_BIND_GROUP_LAYOUT).unwrap();
 
     let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Render Pipeline Layout"),
-        bind_group_layouts: &[camera_bind_group_layout, model_bind_group_layout, material_bind_group_layout],
+        bind_group_layouts: &[
+            camera_bind_group_layout,
+            model_bind_group_layout,
+            material_bind_group_layout,
+            mesh_bind_group_layout,
+        ],
         push_constant_ranges: &[],
     });
 
     let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("animation_shader.wgsl"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("animation_shader_2.wgsl").into()),
+        source: wgpu::ShaderSource::Wgsl(shader_source(context, skinning_method, include_str!("animation_shader_2.wgsl")).into()),
     });
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
+    let swapchain_format = context.config.format;
 
     let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
@@ -254,14 +263,17 @@ pub fn create_render_pipeline_2(context: &GpuContext) -> RenderPipeline {
     render_pipeline
 }
 
+/// The shader with the model bind group declarations for the device and the skinning for the model's method.
+fn shader_source(context: &GpuContext, skinning_method: SkinningMethod, shader: &str) -> String {
+    format!("{}\n{}", skinning_wgsl(context.supports_vertex_storage(), skinning_method), shader)
+}
+
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 pub fn create_depth_texture_view(context: &GpuContext) -> TextureView {
-    let size = context.window.inner_size();
-
     let size = wgpu::Extent3d {
-        width: size.width,
-        height: size.height,
+        width: context.size.width,
+        height: context.size.height,
         depth_or_array_layers: 1,
     };
 
@@ -7,6 +7,7 @@ struct VertexInput {
     @location(4) bitangent: vec3<f32>,
     @location(5) bone_ids: vec4<i32>,
     @location(6) weights: vec4<f32>,
+    @builtin(vertex_index) vertex_index: u32,
 }
 
 struct CameraUniform {
@@ -15,25 +16,19 @@ struct CameraUniform {
    position: vec3<f32>,
 }
 
-const MAX_BONES = 100;
-const MAX_BONE_INFLUENCE = 4;
-
 // camera
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
 
-// model transforms
-@group(1) @binding(0) var<uniform> model_transform: mat4x4<f32>;
-@group(1) @binding(1) var<uniform> node_transform: mat4x4<f32>;
-@group(1) @binding(2) var<uniform> bone_transforms: array<mat4x4<f32>, MAX_BONES>;
+// model transforms, group 1, the mesh's morph targets, group 3, and skin_model_vertex are declared by skinning_wgsl
 
 // material information
 @group(2) @binding(0) var diffuse_texture: texture_2d<f32>;
 @group(2) @binding(1) var diffuse_sampler: sampler;
 
-
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
 };
 
 // Vertex shader
@@ -42,47 +37,33 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
     var result: VertexOutput;
 
-    var anim_position = get_animated_position(model);
+    var input: SkinInput;
+    input.position = model.position;
+    input.normal = model.normal;
+    input.tangent = model.tangent;
+    input.bitangent = model.bitangent;
+    input.bone_ids = model.bone_ids;
+    input.weights = model.weights;
+    input.vertex_index = model.vertex_index;
+
+    let skinned = skin_model_vertex(input);
+    let normal_matrix = mat3x3<f32>(model_transform[0].xyz, model_transform[1].xyz, model_transform[2].xyz);
 
-    result.position = camera.projection * camera.view * model_transform * anim_position;
+    result.position = camera.projection * camera.view * model_transform * skinned.position;
     result.tex_coords = model.tex_coords;
+    result.world_normal = safe_normalize(normal_matrix * skinned.normal);
 
     return result;
 }
 
-fn get_animated_position(model: VertexInput) -> vec4<f32> {
-    var initial_postion = vec4<f32>(0.0);
-    var totalPosition = initial_postion;
-    var localNormal = vec3<f32>(0.0);
-
-    for (var i = 0; i < MAX_BONE_INFLUENCE; i++)
-    {
-        if (model.bone_ids[i] == -1) {
-            continue;
-        }
-
-        if (model.bone_ids[i] >= MAX_BONES) {
-            totalPosition = vec4<f32>(model.position, 1.0f);
-            break;
-        }
-
-        var localPosition = bone_transforms[model.bone_ids[i]] * vec4<f32>(model.position, 1.0f);
-        totalPosition += localPosition * model.weights[i];
-
-//    localNormal = mat3(bone_transforms[bone_ids[i]]) * norm;
-    }
-
-    if (all(totalPosition == initial_postion)) {
-        totalPosition = node_transform * vec4<f32>(model.position, 1.0f);
-    }
-
-    return totalPosition;
-}
-
 // Fragment shader
+const LIGHT_DIRECTION = vec3<f32>(0.3, 1.0, 0.5);
+const AMBIENT = 0.3;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords);
     color = vec4(0.3, 0.3, 0.3, 1.0);
-    return color;
+    let diffuse = max(dot(safe_normalize(in.world_normal), normalize(LIGHT_DIRECTION)), 0.0);
+    return vec4<f32>(color.rgb * (AMBIENT + (1.0 - AMBIENT) * diffuse), color.a);
 }
@@ -25,7 +25,7 @@ pub const BACKGROUND_COLOR: wgpu::Color = wgpu::Color {
 pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
     let mut context = GpuContext::new(window).await;
     let mut frame_counter = FrameCounter::new();
-    let size = context.window.inner_size();
+    let size = context.size;
     let aspect_ratio = size.width as f32 / size.height as f32;
 
     let camera_position = vec3(0.0, 100.0, 300.0);
@@ -44,7 +44,8 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
 
     let depth_texture_view = create_depth_texture_view(&context);
 
-    let anim_render = AnimRenderPass::new(&mut context);
+    let skinning_method = model.animator.borrow().skinning_method;
+    let anim_render = AnimRenderPass::new(&mut context, skinning_method);
 
     #[allow(unused_mut)]
     let mut model_transform = Mat4::IDENTITY;
@@ -87,7 +88,7 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
                             world.camera_controller.resize(&context);
                             world.camera_handler.update_camera(&context, &world.camera_controller);
                             world.depth_texture_view = create_depth_texture_view(&context);
-                            context.window.request_redraw();
+                            context.request_redraw();
                         }
                         WindowEvent::RedrawRequested => {
                             frame_counter.update();
@@ -102,7 +103,7 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
 
                             anim_render.render(&context, &world);
 
-                            context.window.request_redraw();
+                            context.request_redraw();
 
                             // println!("Input: {:#?}\n", &world.input);
                         }
@@ -25,7 +25,7 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
     let mut context = GpuContext::new(window).await;
     let mut frame_counter = FrameCounter::new();
 
-    let size = context.window.inner_size();
+    let size = context.size;
     let aspect_ratio = size.width as f32 / size.height as f32;
 
     let camera_position = vec3(1.5, 1.5, 5.0);
@@ -48,14 +48,14 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
                         context.resize(new_size);
                         camera_handler.update_camera(&context, &camera_controller);
                         depth_texture = create_depth_texture(&context);
-                        context.window.request_redraw();
+                        context.request_redraw();
                     }
                     WindowEvent::RedrawRequested => {
                         frame_counter.update();
 
                         draw(&context, &render_pipeline, &camera_handler, &model, &depth_texture);
 
-                        context.window.request_redraw();
+                        context.request_redraw();
                     }
                     WindowEvent::KeyboardInput { event, .. } => {
                         // if event.state == ElementState::Pressed {
@@ -79,12 +79,9 @@ pub fn draw(
     model: &Model,
     depth_texture: &Texture,
 ) {
-    let frame = context
-        .surface
-        .get_current_texture()
-        .expect("Failed to acquire next swap chain texture");
+    let frame = context.get_current_frame();
 
-    let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let view = frame.create_view();
 
     let mut encoder = context
         .device
@@ -147,8 +144,7 @@ pub fn create_render_pipeline(
         source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
     });
 
-    let swapchain_capabilities = context.surface.get_capabilities(&context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
+    let swapchain_format = context.config.format;
 
     let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
@@ -1,10 +1,36 @@
 use gltf::Gltf;
+use spark_gap::gpu_context::GpuContext;
+use spark_gap::model_builder::ModelBuilder;
 
 fn main() {
-    let gltf = Gltf::open("/Users/john/Dev/Assets/glTF-Sample-Models/2.0/CesiumMan/glTF/CesiumMan.gltf").unwrap();
+    env_logger::init();
+
+    let model_path = std::env::args()
+        .nth(1)
+        .unwrap_or("/Users/john/Dev/Assets/glTF-Sample-Models/2.0/CesiumMan/glTF/CesiumMan.gltf".to_string());
+
+    let gltf = Gltf::open(&model_path).unwrap();
     for scene in gltf.scenes() {
         for node in scene.nodes() {
             println!("Node #{} has {} children", node.index(), node.children().count(),);
         }
     }
+
+    let mut context = pollster::block_on(GpuContext::new_headless(64, 64, false));
+
+    let model = ModelBuilder::new("model", &model_path)
+        .use_gltf_loader()
+        .build(&mut context)
+        .unwrap();
+
+    let animator = model.animator.borrow();
+
+    println!("Meshes: {}", model.meshes.len());
+    println!("Bones: {}", animator.bone_data_map.borrow().len());
+    for animation in animator.animations.iter() {
+        println!(
+            "Animation: {}  duration: {}  ticks per second: {}",
+            animation.name, animation.duration, animation.ticks_per_second
+        );
+    }
 }
@@ -14,7 +14,7 @@ struct VertexOutput {
 @group(0) @binding(1) var<uniform> model_transform: mat4x4<f32>;
 @group(0) @binding(2) var<uniform> layer_num: u32;
 
-@group(0) @binding(3) var texture: texture_depth_2d_array;
+@group(0) @binding(3) var texture: texture_2d_array<f32>;
 @group(0) @binding(4) var texture_sampler: sampler;
 
 
@@ -33,7 +33,10 @@ struct VertexOutput {
     let flip_correction = vec2<f32>(1.0, -1.0);
     let tex_coords = in.tex_coords * flip_correction + vec2<f32>(0.0, 1.0);
 
-    var value = textureSample(texture, texture_sampler, tex_coords, layer_num);
+    // loaded rather than sampled, sampling a depth texture without a comparison isn't supported on GL
+    let size = vec2<f32>(textureDimensions(texture));
+    let texel = clamp(vec2<i32>(tex_coords * size), vec2<i32>(0), vec2<i32>(size) - 1);
+    var value = textureLoad(texture, texel, i32(layer_num), 0).r;
 
     // expand top range and reverse the range for better grayscale contrast
     value = 1.0 - (value - 0.80) * 5.0;
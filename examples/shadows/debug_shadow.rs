@@ -165,7 +165,7 @@ fn create_shadow_filter_bind_group_layout(context: &GpuContext, label: &str) ->
                 visibility: wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Texture {
                     multisampled: false,
-                    sample_type: wgpu::TextureSampleType::Depth,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
                     view_dimension: wgpu::TextureViewDimension::D2Array,
                 },
                 count: None,
@@ -196,8 +196,7 @@ pub fn create_debug_depth_render_pipeline(gpu_context: &GpuContext) -> RenderPip
         push_constant_ranges: &[],
     });
 
-    let swapchain_capabilities = gpu_context.surface.get_capabilities(&gpu_context.adapter);
-    let swapchain_format = swapchain_capabilities.formats[0];
+    let swapchain_format = gpu_context.config.format;
 
     let render_pipeline = gpu_context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("debug texture pipeline"),
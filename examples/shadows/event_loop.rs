@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use winit::event::{ElementState, Event, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::keyboard::KeyCode::{Digit1, Digit2, Escape, Space, KeyC};
+use winit::keyboard::KeyCode::{Digit1, Digit2, Escape, KeyC, Space, F12};
 use winit::keyboard::PhysicalKey;
 use winit::window::Window;
 
@@ -24,14 +24,14 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
                     WindowEvent::Resized(new_size) => {
                         context.resize(new_size);
                         world.resize(&context);
-                        context.window.request_redraw();
+                        context.request_redraw();
                     }
                     WindowEvent::RedrawRequested => {
                         frame_counter.update();
 
                         world.render(&context);
 
-                        context.window.request_redraw();
+                        context.request_redraw();
                     }
                     WindowEvent::KeyboardInput { event, .. } => {
                         if event.state == ElementState::Pressed {
@@ -40,6 +40,7 @@ pub async fn run(event_loop: EventLoop<()>, window: Arc<Window>) {
                                 PhysicalKey::Code(Space) => world.show_shadows = !world.show_shadows,
                                 PhysicalKey::Code(Digit1) => world.layer_number = 0,
                                 PhysicalKey::Code(Digit2) => world.layer_number = 1,
+                                PhysicalKey::Code(F12) => world.take_screenshot = true,
                                 PhysicalKey::Code(KeyC) => {
                                     world.camera_position += 1;
                                     if world.camera_position > 2 { world.camera_position = 0; }
@@ -20,6 +20,7 @@ pub fn main() {
         c : switch camera from normal, light 1 position, light 2 position
         space : toggle between normal display and shadow map display
         0, 1 : select shadow map layer
+        F12 : save a screenshot
     ");
 
     env_logger::init();
@@ -1,10 +1,11 @@
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{borrow::Cow, f32::consts, iter, mem};
 
 use glam::{vec3, Mat4, Vec3};
 use wgpu::TextureView;
 
 use spark_gap::buffers::{update_mat4_buffer, update_u32_buffer};
-use spark_gap::gpu_context::GpuContext;
+use spark_gap::gpu_context::{Frame, GpuContext};
 use spark_gap::texture::DEPTH_FORMAT;
 
 use crate::cube::Vertex;
@@ -24,6 +25,7 @@ pub struct World {
     pub show_shadows: bool,
     pub layer_number: u32,
     pub camera_position: u32,
+    pub take_screenshot: bool,
 }
 
 impl World {
@@ -61,6 +63,7 @@ impl World {
             show_shadows: false,
             layer_number: 0,
             camera_position: 0,
+            take_screenshot: false,
         }
     }
 
@@ -120,12 +123,9 @@ impl World {
         // forward pass
         encoder.push_debug_group("forward rendering pass");
 
-        let frame = context
-            .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
+        let frame = context.get_current_frame();
 
-        let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let frame_view = frame.create_view();
 
         {
             let color_attachment = wgpu::RenderPassColorAttachment {
@@ -201,6 +201,12 @@ impl World {
         encoder.pop_debug_group();
 
         context.queue.submit(iter::once(encoder.finish()));
+
+        if self.take_screenshot {
+            self.take_screenshot = false;
+            save_screenshot(context, &frame);
+        }
+
         frame.present();
     }
 
@@ -244,6 +250,15 @@ pub fn get_projection_view_matrix(aspect_ratio: f32) -> Mat4 {
     projection * view
 }
 
+fn save_screenshot(context: &GpuContext, frame: &Frame) {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let filename = format!("screenshot_{}.png", seconds);
+    match context.save_frame(frame, &filename) {
+        Ok(()) => println!("saved {}", filename),
+        Err(e) => println!("screenshot failed: {}", e),
+    }
+}
+
 fn create_depth_texture(gpu_context: &GpuContext) -> TextureView {
     let depth_texture = gpu_context.device.create_texture(&wgpu::TextureDescriptor {
         size: wgpu::Extent3d {
@@ -0,0 +1,341 @@
+use crate::animator::{AnimationClip, Animator};
+use crate::hash_map::HashMap;
+use log::debug;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// The bool parameter has the value.
+    Bool(String, bool),
+    /// The float parameter is greater than the value.
+    Greater(String, f32),
+    /// The float parameter is less than the value.
+    Less(String, f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationState {
+    pub name: Rc<str>,
+    pub clip: Rc<AnimationClip>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StateTransition {
+    /// None for a transition that can be taken from any state.
+    pub from: Option<Rc<str>>,
+    pub to: Rc<str>,
+    pub duration: Duration,
+    /// All the conditions must hold for the transition to be taken.
+    pub conditions: Vec<Condition>,
+    /// How much of the current clip has to be played before the transition is taken, in loops of the clip.
+    /// 1.0 is the end of the first loop, 2.5 halfway through the third loop of a repeating clip.
+    pub exit_time: Option<f32>,
+}
+
+impl StateTransition {
+    pub fn new(from: &str, to: &str, duration: Duration) -> Self {
+        StateTransition {
+            from: Some(Rc::from(from)),
+            to: Rc::from(to),
+            duration,
+            conditions: vec![],
+            exit_time: None,
+        }
+    }
+
+    pub fn from_any_state(to: &str, duration: Duration) -> Self {
+        StateTransition {
+            from: None,
+            to: Rc::from(to),
+            duration,
+            conditions: vec![],
+            exit_time: None,
+        }
+    }
+
+    pub fn when(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_exit_time(mut self, exit_time: f32) -> Self {
+        self.exit_time = Some(exit_time);
+        self
+    }
+}
+
+/// Plays the clip of the current state on an [`Animator`] and moves between states when the
+/// transition conditions on the parameters are met. Transitions from any state are checked first.
+#[derive(Debug, Clone)]
+pub struct AnimationStateMachine {
+    pub states: Vec<AnimationState>,
+    pub transitions: Vec<StateTransition>,
+    bool_parameters: HashMap<String, bool>,
+    float_parameters: HashMap<String, f32>,
+    current_state: usize,
+    started: bool,
+}
+
+impl Default for AnimationStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnimationStateMachine {
+    pub fn new() -> Self {
+        AnimationStateMachine {
+            states: vec![],
+            transitions: vec![],
+            bool_parameters: HashMap::new(),
+            float_parameters: HashMap::new(),
+            current_state: 0,
+            started: false,
+        }
+    }
+
+    /// Adds a state, the first state added is the starting state.
+    pub fn add_state(mut self, name: &str, clip: &Rc<AnimationClip>) -> Self {
+        self.states.push(AnimationState {
+            name: Rc::from(name),
+            clip: clip.clone(),
+        });
+        self
+    }
+
+    pub fn add_transition(mut self, transition: StateTransition) -> Self {
+        self.transitions.push(transition);
+        self
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.bool_parameters.insert(name.to_string(), value);
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.float_parameters.insert(name.to_string(), value);
+    }
+
+    /// Unset parameters are false.
+    pub fn get_bool(&self, name: &str) -> bool {
+        self.bool_parameters.get(name).copied().unwrap_or(false)
+    }
+
+    /// Unset parameters are 0.0.
+    pub fn get_float(&self, name: &str) -> f32 {
+        self.float_parameters.get(name).copied().unwrap_or(0.0)
+    }
+
+    pub fn current_state(&self) -> Option<&AnimationState> {
+        self.states.get(self.current_state)
+    }
+
+    /// Takes any transition whose conditions are met, then updates the animator.
+    pub fn update(&mut self, animator: &mut Animator, delta_time: f32) {
+        if self.states.is_empty() {
+            animator.update_animation(delta_time);
+            return;
+        }
+
+        if !self.started {
+            self.started = true;
+            animator.play_clip(&self.states[self.current_state].clip);
+        } else if let Some((state_index, duration)) = self.find_transition(animator) {
+            let state = &self.states[state_index];
+            debug!("animation state: {} -> {}", self.states[self.current_state].name, state.name);
+
+            if duration.is_zero() {
+                animator.play_clip(&state.clip);
+            } else {
+                animator.play_clip_with_transition(&state.clip, duration);
+            }
+            self.current_state = state_index;
+        }
+
+        animator.update_animation(delta_time);
+    }
+
+    fn find_transition(&self, animator: &Animator) -> Option<(usize, Duration)> {
+        let current_name = &self.states[self.current_state].name;
+
+        let any_state_transitions = self.transitions.iter().filter(|t| t.from.is_none() && t.to != *current_name);
+        let state_transitions = self.transitions.iter().filter(|t| t.from.as_ref() == Some(current_name));
+
+        any_state_transitions
+            .chain(state_transitions)
+            .filter(|transition| self.can_transition(transition, animator))
+            .find_map(|transition| {
+                let state_index = self.states.iter().position(|s| s.name == transition.to);
+                if state_index.is_none() {
+                    debug!("animation state not found: {}", transition.to);
+                }
+                state_index.map(|index| (index, transition.duration))
+            })
+    }
+
+    fn can_transition(&self, transition: &StateTransition, animator: &Animator) -> bool {
+        if let Some(exit_time) = transition.exit_time {
+            if clip_progress(animator) < exit_time {
+                return false;
+            }
+        }
+
+        transition.conditions.iter().all(|condition| match condition {
+            Condition::Bool(name, value) => self.get_bool(name) == *value,
+            Condition::Greater(name, value) => self.get_float(name) > *value,
+            Condition::Less(name, value) => self.get_float(name) < *value,
+        })
+    }
+}
+
+/// How far through its clip the current animation is, the loops played and the fraction of the current one.
+fn clip_progress(animator: &Animator) -> f32 {
+    let playing = &animator.current_animation;
+    let clip = &playing.animation_clip;
+    let length = clip.end_tick - clip.start_tick;
+    let completions = playing.repeat_completions as f32;
+
+    // a finished clip holds the end of its last loop
+    if playing.current_tick < 0.0 || length <= 0.0 || playing.is_finished() {
+        return completions;
+    }
+    completions + (playing.current_tick - clip.start_tick) / length
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation_state_machine::{AnimationStateMachine, Condition, StateTransition};
+    use crate::animator::{AnimationClip, AnimationRepeat, Animator};
+    use crate::hash_map::HashMap;
+    use crate::model_animation::{ModelAnimation, NodeData};
+    use crate::transform::Transform;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    fn test_animator() -> Animator {
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![],
+            meshes: Rc::new(vec![]),
+        };
+
+        let animation = ModelAnimation {
+            name: Rc::from("all"),
+            duration: 100.0,
+            ticks_per_second: 10.0,
+            node_animations: RefCell::new(vec![]),
+        };
+
+        Animator::from_node_data(root_node, vec![animation], RefCell::new(HashMap::new()))
+    }
+
+    fn test_state_machine() -> AnimationStateMachine {
+        let idle = Rc::new(AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever));
+        let run = Rc::new(AnimationClip::new(10.0, 20.0, AnimationRepeat::Forever));
+        let shoot = Rc::new(AnimationClip::new(20.0, 30.0, AnimationRepeat::Once));
+        let die = Rc::new(AnimationClip::new(30.0, 40.0, AnimationRepeat::Once));
+
+        AnimationStateMachine::new()
+            .add_state("idle", &idle)
+            .add_state("run", &run)
+            .add_state("shoot", &shoot)
+            .add_state("die", &die)
+            .add_transition(StateTransition::new("idle", "run", Duration::from_millis(200)).when(Condition::Greater("speed".into(), 0.1)))
+            .add_transition(StateTransition::new("run", "idle", Duration::from_millis(200)).when(Condition::Less("speed".into(), 0.1)))
+            .add_transition(StateTransition::new("idle", "shoot", Duration::ZERO).when(Condition::Bool("shoot".into(), true)))
+            .add_transition(StateTransition::new("shoot", "idle", Duration::ZERO).with_exit_time(1.0))
+            .add_transition(
+                StateTransition::from_any_state("die", Duration::from_millis(100)).when(Condition::Bool("is_dead".into(), true)),
+            )
+    }
+
+    fn current_state(state_machine: &AnimationStateMachine) -> &str {
+        &state_machine.current_state().unwrap().name
+    }
+
+    #[test]
+    fn test_parameter_transitions() {
+        let mut animator = test_animator();
+        let mut state_machine = test_state_machine();
+
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "idle");
+        assert_eq!(animator.current_animation.animation_clip.start_tick, 0.0);
+
+        state_machine.set_float("speed", 1.0);
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "run");
+        assert_eq!(animator.current_animation.animation_clip.start_tick, 10.0);
+        assert_eq!(animator.transitions.borrow().len(), 1);
+
+        state_machine.set_float("speed", 0.0);
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "idle");
+    }
+
+    #[test]
+    fn test_exit_time_transition() {
+        let mut animator = test_animator();
+        let mut state_machine = test_state_machine();
+
+        state_machine.update(&mut animator, 0.0);
+        state_machine.set_bool("shoot", true);
+        state_machine.update(&mut animator, 0.0);
+        assert_eq!(current_state(&state_machine), "shoot");
+
+        // shoot stays until its clip has played through
+        state_machine.set_bool("shoot", false);
+        state_machine.update(&mut animator, 0.5);
+        assert_eq!(current_state(&state_machine), "shoot");
+
+        state_machine.update(&mut animator, 1.0);
+        state_machine.update(&mut animator, 0.0);
+        assert_eq!(current_state(&state_machine), "idle");
+    }
+
+    #[test]
+    fn test_exit_time_on_looping_clip() {
+        let mut animator = test_animator();
+        let idle = Rc::new(AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever));
+        let run = Rc::new(AnimationClip::new(10.0, 20.0, AnimationRepeat::Forever));
+
+        let mut state_machine = AnimationStateMachine::new()
+            .add_state("idle", &idle)
+            .add_state("run", &run)
+            .add_transition(StateTransition::new("idle", "run", Duration::ZERO).with_exit_time(1.5));
+
+        state_machine.update(&mut animator, 0.0);
+
+        // idle loops at tick 10, the wrapped tick alone never gets past 1.0
+        state_machine.update(&mut animator, 1.2);
+        assert_eq!(current_state(&state_machine), "idle");
+        assert_eq!(animator.current_animation.repeat_completions, 1);
+
+        state_machine.update(&mut animator, 0.4);
+        state_machine.update(&mut animator, 0.0);
+        assert_eq!(current_state(&state_machine), "run");
+    }
+
+    #[test]
+    fn test_any_state_transition() {
+        let mut animator = test_animator();
+        let mut state_machine = test_state_machine();
+
+        state_machine.set_float("speed", 1.0);
+        state_machine.update(&mut animator, 0.1);
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "run");
+
+        state_machine.set_bool("is_dead", true);
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "die");
+
+        // the any state transition doesn't restart the state it leads to
+        state_machine.update(&mut animator, 0.1);
+        assert_eq!(current_state(&state_machine), "die");
+        assert!((animator.current_animation.current_tick - 32.0).abs() < 1e-4);
+    }
+}
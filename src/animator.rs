@@ -1,12 +1,20 @@
-use crate::hash_map::HashMap;
+use crate::dual_quat::DualQuat;
+use crate::hash_map::{HashMap, HashSet};
+use crate::ik::IkConstraint;
 use crate::model_animation::{BoneData, BoneName, ModelAnimation, NodeData};
 use crate::node_animation::NodeAnimation;
+use crate::pose::{flatten_nodes, global_pose, CapturedPose, PoseBlend, PoseNode};
+use crate::pose_cache::{animation_id, skeleton_id, CachedPose, PoseCache, PoseKey};
+use crate::retarget::BindPose;
+use crate::skinning::SkinningMethod;
 use crate::transform::Transform;
 use crate::utils::min;
-use glam::Mat4;
+use glam::{Mat4, Quat, Vec3};
+#[cfg(feature = "russimp")]
 use russimp::node::Node;
+#[cfg(feature = "russimp")]
 use russimp::scene::Scene;
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::RefCell;
 use std::ops::Deref;
 use std::rc::Rc;
 use std::time::Duration;
@@ -14,29 +22,76 @@ use std::time::Duration;
 #[derive(Debug, Clone)]
 pub enum AnimationRepeat {
     Once,
+    /// Plays the clip the given number of times, then holds the last frame.
     Count(u32),
     Forever,
 }
 
+/// A named marker on a clip, such as a footstep, that fires when playback crosses its tick.
+#[derive(Debug, Clone)]
+pub struct AnimationEvent {
+    pub name: Rc<str>,
+    pub tick: f32,
+}
+
+/// An event that fired during an update, with the clip it came from and the clip's weight,
+/// which is below 1.0 for clips that are fading out in a transition.
+#[derive(Debug, Clone)]
+pub struct FiredAnimationEvent {
+    pub name: Rc<str>,
+    pub tick: f32,
+    pub weight: f32,
+    pub animation_clip: Rc<AnimationClip>,
+}
+
 #[derive(Debug, Clone)]
 pub struct AnimationClip {
+    pub animation_index: usize,
     pub start_tick: f32,
     pub end_tick: f32,
     pub repeat: AnimationRepeat,
+    pub events: Vec<AnimationEvent>,
 }
 
 impl AnimationClip {
+    /// A tick range of the first animation, use `with_animation` to select another.
     pub fn new(start_tick: f32, end_tick: f32, repeat: AnimationRepeat) -> Self {
         AnimationClip {
+            animation_index: 0,
             start_tick,
             end_tick,
             repeat,
+            events: vec![],
+        }
+    }
+
+    pub fn with_animation(mut self, animation_index: usize) -> Self {
+        self.animation_index = animation_index;
+        self
+    }
+
+    pub fn with_event(mut self, name: &str, tick: f32) -> Self {
+        self.events.push(AnimationEvent {
+            name: Rc::from(name),
+            tick,
+        });
+        self
+    }
+
+    /// Adds the events with ticks after `from`, or at `from` when `include_from` is set, up to and including `to`.
+    fn collect_events(&self, from: f32, to: f32, include_from: bool, events: &mut Vec<AnimationEvent>) {
+        for event in self.events.iter() {
+            let after_from = event.tick > from || (include_from && event.tick == from);
+            if after_from && event.tick <= to {
+                events.push(event.clone());
+            }
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct WeightedAnimation {
+    pub animation_index: usize,
     pub weight: f32,
     pub start_tick: f32,
     pub end_tick: f32,
@@ -47,6 +102,7 @@ pub struct WeightedAnimation {
 impl WeightedAnimation {
     pub fn new(weight: f32, start_tick: f32, end_tick: f32, offset: f32, optional_start: f32) -> Self {
         WeightedAnimation {
+            animation_index: 0,
             weight,
             start_tick,
             end_tick,
@@ -54,6 +110,11 @@ impl WeightedAnimation {
             optional_start, // used for non-looped animations
         }
     }
+
+    pub fn with_animation(mut self, animation_index: usize) -> Self {
+        self.animation_index = animation_index;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -61,32 +122,121 @@ pub struct PlayingAnimation {
     pub animation_clip: Rc<AnimationClip>,
     pub current_tick: f32,
     pub ticks_per_second: f32,
+    /// The number of times the clip has played through to its end.
     pub repeat_completions: u32,
 }
 
 impl PlayingAnimation {
-    pub fn update(&mut self, delta_time: f32) {
+    /// True once a clip that doesn't repeat forever has played all its loops and is holding its last frame.
+    pub fn is_finished(&self) -> bool {
+        match self.animation_clip.repeat {
+            AnimationRepeat::Once => self.repeat_completions >= 1,
+            AnimationRepeat::Count(count) => self.repeat_completions >= count.max(1),
+            AnimationRepeat::Forever => false,
+        }
+    }
+
+    /// Advances the tick and adds the clip events that were crossed to `events`. A looping clip
+    /// carries the ticks past its end into the next loop, firing the events of every loop passed.
+    pub fn update(&mut self, delta_time: f32, events: &mut Vec<AnimationEvent>) {
+        if self.is_finished() {
+            return;
+        }
+
+        let clip = &self.animation_clip;
+
+        // the start tick itself is included when the clip starts or loops
+        let mut include_from = false;
+
         if self.current_tick < 0.0 {
-            self.current_tick = self.animation_clip.start_tick;
+            self.current_tick = clip.start_tick;
+            include_from = true;
         }
 
-        self.current_tick += self.ticks_per_second * delta_time;
+        let mut from = self.current_tick;
+        let mut to = from + self.ticks_per_second * delta_time;
 
-        if self.current_tick > self.animation_clip.end_tick {
-            match self.animation_clip.repeat {
-                AnimationRepeat::Once => {
-                    self.current_tick = self.animation_clip.end_tick;
-                }
-                AnimationRepeat::Count(_) => {}
-                AnimationRepeat::Forever => {
-                    self.current_tick = self.animation_clip.start_tick;
-                }
+        loop {
+            clip.collect_events(from, to.min(clip.end_tick), include_from, events);
+
+            if to <= clip.end_tick {
+                break;
+            }
+
+            self.repeat_completions = self.repeat_completions.saturating_add(1);
+
+            let finished = match clip.repeat {
+                AnimationRepeat::Once => true,
+                AnimationRepeat::Count(count) => self.repeat_completions >= count.max(1),
+                AnimationRepeat::Forever => false,
+            };
+
+            if finished {
+                to = clip.end_tick;
+                break;
             }
-            // in ticks
+
+            if clip.end_tick <= clip.start_tick {
+                to = clip.start_tick;
+                break;
+            }
+
+            to = clip.start_tick + (to - clip.end_tick);
+            from = clip.start_tick;
+            include_from = true;
+        }
+
+        self.current_tick = to;
+    }
+}
+
+/// The nodes an animation layer applies to.
+#[derive(Debug, Clone)]
+pub enum BoneMask {
+    All,
+    /// Only the named nodes.
+    Nodes(HashSet<Rc<str>>),
+    /// The named node and all the nodes under it, such as "Spine" for the upper body.
+    Subtree(Rc<str>),
+}
+
+impl BoneMask {
+    pub fn nodes(node_names: &[&str]) -> Self {
+        BoneMask::Nodes(node_names.iter().map(|name| Rc::from(*name)).collect())
+    }
+
+    pub fn subtree(node_name: &str) -> Self {
+        BoneMask::Subtree(Rc::from(node_name))
+    }
+
+    fn contains(&self, node_name: &Rc<str>, parent_masked: bool) -> bool {
+        match self {
+            BoneMask::All => true,
+            BoneMask::Nodes(node_names) => node_names.contains(node_name),
+            BoneMask::Subtree(root_name) => parent_masked || root_name == node_name,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerBlend {
+    /// Blends the masked nodes toward the layer pose by the weight.
+    Override,
+    /// Adds the scaled deltas of an additive animation, see `Animator::add_additive_animation`.
+    Additive,
+}
+
+/// A clip played on top of the base pose for the nodes in its mask, such as shooting on the upper body
+/// while the legs run. At a weight of 1.0 an override layer replaces the masked nodes.
+#[derive(Debug, Clone)]
+pub struct AnimationLayer {
+    pub name: Rc<str>,
+    pub animation: PlayingAnimation,
+    pub weight: f32,
+    pub mask: BoneMask,
+    pub blend: LayerBlend,
+}
+
 /// An animation that is being faded out as part of a transition (from Bevy)
 #[derive(Debug, Clone)]
 pub struct AnimationTransition {
@@ -98,6 +248,16 @@ pub struct AnimationTransition {
     pub animation: PlayingAnimation,
 }
 
+/// A captured pose that is being faded out, such as the pose a character died in blending into its death clip.
+#[derive(Debug, Clone)]
+pub struct PoseTransition {
+    /// The current weight. Starts at 1.0 and goes to 0.0 during the fade-out.
+    pub current_weight: f32,
+    /// How much to decrease `current_weight` per second
+    pub weight_decline_per_sec: f32,
+    pub pose: Rc<CapturedPose>,
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeTransform {
     pub transform: Transform,
@@ -113,121 +273,334 @@ impl NodeTransform {
     }
 }
 
+/// The palette sizes of the uniform buffer fallback, for devices without storage buffers in vertex shaders.
+/// The palettes themselves are sized from the model.
 pub const MAX_BONES: usize = 100;
 pub const MAX_NODES: usize = 50;
 
 #[derive(Debug, Clone)]
 pub struct Animator {
     pub root_node: NodeData,
+    /// The nodes of `root_node` flattened, the order of the local poses.
+    pub pose_nodes: Vec<PoseNode>,
+    /// Keeps the poses of different skeletons apart in a shared pose cache, see `pose_cache::skeleton_id`.
+    pub skeleton_id: u64,
+    /// For each animation, the index of each pose node's channel in its node animations.
+    pub animation_channels: Vec<Rc<[Option<usize>]>>,
     pub global_inverse_transform: Mat4,
     pub bone_data_map: RefCell<HashMap<BoneName, BoneData>>,
 
-    pub model_animation: ModelAnimation, // maybe should be vec?
+    /// All the animations of the model, there is always at least one.
+    pub animations: Vec<Rc<ModelAnimation>>,
 
     pub current_animation: PlayingAnimation,
     pub transitions: RefCell<Vec<AnimationTransition>>,
+    /// Fades out under the transitions, see `blend_from_pose`.
+    pub pose_transition: Option<PoseTransition>,
+    /// Held instead of the current animation, see `hold_pose`.
+    pub held_pose: Option<Rc<CapturedPose>>,
 
+    /// The local pose of the last update, in the order of `pose_nodes`. Stale while `cached_pose` holds the pose.
+    pub local_pose: RefCell<Vec<Transform>>,
+    /// Stale while `cached_pose` holds the pose, `get_node_transform` reads either.
     pub node_transforms: RefCell<HashMap<Rc<str>, NodeTransform>>,
 
+    /// The bone palette, indexed by bone index, with room for every bone of the model.
     pub final_bone_matrices: RefCell<Box<[Mat4]>>,
+    /// The bone palette as dual quaternions, only filled for dual quaternion skinning.
+    pub final_bone_dual_quats: RefCell<Box<[DualQuat]>>,
+    pub skinning_method: SkinningMethod,
+    /// The node palette, indexed by mesh id, with room for every mesh of the model.
     pub final_node_matrices: RefCell<Box<[Mat4]>>,
+
+    /// Events fired by the updates since the last `drain_events`.
+    pub fired_events: RefCell<Vec<FiredAnimationEvent>>,
+
+    /// Clips that finished playing since the last `drain_completed_clips`.
+    pub completed_clips: RefCell<Vec<Rc<AnimationClip>>>,
+
+    /// Played, with the transition duration, when the current clip finishes. Without it the last frame is held.
+    pub fallback_clip: Option<(Rc<AnimationClip>, Duration)>,
+
+    /// The node whose animated translation and rotation are taken out of the pose as root motion.
+    pub root_motion_node: Option<Rc<str>>,
+
+    /// Root motion accumulated by the updates since the last `take_root_motion`.
+    pub root_motion: RefCell<Transform>,
+
+    /// The tick each weighted animation was sampled at by the last `play_weight_animations`, by animation
+    /// index and tick range, for their root motion.
+    pub weighted_ticks: HashMap<(usize, u32, u32), f32>,
+
+    /// Applied in order on top of the current animation and transitions.
+    pub layers: Vec<AnimationLayer>,
+
+    /// Named constraints solved in order on the sampled pose.
+    pub ik_constraints: Vec<(Rc<str>, IkConstraint)>,
+
+    /// The animated morph target weights of each node's meshes, by node name. Layers don't change them.
+    /// Stale while `cached_pose` holds the pose.
+    pub morph_weights: RefCell<HashMap<Rc<str>, Vec<f32>>>,
+
+    /// Morph target weights set by code, used instead of the animated ones.
+    pub morph_weight_overrides: HashMap<Rc<str>, Vec<f32>>,
+
+    /// Shares the sampled poses with the animators of other models of the same skeleton.
+    pub pose_cache: Option<Rc<PoseCache>>,
+
+    /// The pose of the last update when it came from the pose cache, held rather than copied into `local_pose`,
+    /// `node_transforms` and `morph_weights`. The palettes are still copied, they are what the model uploads.
+    pub cached_pose: RefCell<Option<Rc<CachedPose>>>,
 }
 
 impl Animator {
+    #[cfg(feature = "russimp")]
     pub fn new(scene: &Scene, bone_data_map: RefCell<HashMap<BoneName, BoneData>>) -> Self {
         let root = scene.root.as_ref().unwrap().clone();
-        let global_inverse_transform = root.transformation.inverse();
         let root_node = read_hierarchy_data(&root);
 
-        let model_animation = ModelAnimation::new(scene);
+        let animations = ModelAnimation::from_scene(scene);
+
+        Animator::from_node_data(root_node, animations, bone_data_map)
+    }
 
-        let final_bone_matrices = [Mat4::IDENTITY; MAX_BONES];
-        let final_node_matrices = [Mat4::IDENTITY; MAX_NODES];
+    /// Creates the animator from an already converted node hierarchy, for loaders other than russimp.
+    /// Models without animations get an empty default animation, which holds the rest pose.
+    pub fn from_node_data(root_node: NodeData, animations: Vec<ModelAnimation>, bone_data_map: RefCell<HashMap<BoneName, BoneData>>) -> Self {
+        let global_inverse_transform = root_node.transform.compute_matrix().inverse();
 
-        let animation_clip = AnimationClip {
-            start_tick: 0.0,
-            end_tick: model_animation.duration,
-            repeat: AnimationRepeat::Forever,
-        };
+        let mut animations: Vec<Rc<ModelAnimation>> = animations.into_iter().map(Rc::new).collect();
+        if animations.is_empty() {
+            animations.push(Rc::new(ModelAnimation::default()));
+        }
+
+        let pose_nodes = flatten_nodes(&root_node);
+        let skeleton_id = skeleton_id(&pose_nodes, bone_data_map.borrow().values());
+
+        let bone_count = bone_data_map
+            .borrow()
+            .values()
+            .map(|bone_data| bone_data.bone_index + 1)
+            .max()
+            .unwrap_or(0);
+        let mesh_count = pose_nodes
+            .iter()
+            .flat_map(|pose_node| pose_node.meshes.iter())
+            .map(|mesh_id| mesh_id + 1)
+            .max()
+            .unwrap_or(0);
+
+        // never empty, so the palette buffers are never empty
+        let final_bone_matrices = vec![Mat4::IDENTITY; bone_count.max(1) as usize];
+        let final_bone_dual_quats = vec![DualQuat::IDENTITY; bone_count.max(1) as usize];
+        let final_node_matrices = vec![Mat4::IDENTITY; mesh_count.max(1) as usize];
+
+        let animation_clip = AnimationClip::new(0.0, animations[0].duration, AnimationRepeat::Forever);
 
         let current_animation = PlayingAnimation {
             animation_clip: Rc::new(animation_clip),
             current_tick: -1.0,
-            ticks_per_second: model_animation.ticks_per_second,
+            ticks_per_second: animations[0].ticks_per_second,
             repeat_completions: 0,
         };
 
+        let animation_channels = animations.iter().map(|animation| channel_map(&pose_nodes, animation)).collect();
+        let local_pose = pose_nodes.iter().map(|pose_node| pose_node.transform).collect();
+
         Animator {
             root_node,
+            pose_nodes,
+            skeleton_id,
+            animation_channels,
             global_inverse_transform,
             bone_data_map,
-            model_animation,
+            animations,
             current_animation,
             transitions: vec![].into(),
+            pose_transition: None,
+            held_pose: None,
+            local_pose: RefCell::new(local_pose),
             node_transforms: HashMap::new().into(),
-            final_bone_matrices: RefCell::new(Box::new(final_bone_matrices)),
-            final_node_matrices: RefCell::new(Box::new(final_node_matrices)),
+            final_bone_matrices: RefCell::new(final_bone_matrices.into_boxed_slice()),
+            final_bone_dual_quats: RefCell::new(final_bone_dual_quats.into_boxed_slice()),
+            skinning_method: SkinningMethod::default(),
+            final_node_matrices: RefCell::new(final_node_matrices.into_boxed_slice()),
+            fired_events: vec![].into(),
+            completed_clips: vec![].into(),
+            fallback_clip: None,
+            root_motion_node: None,
+            root_motion: Transform::IDENTITY.into(),
+            weighted_ticks: HashMap::new(),
+            layers: vec![],
+            ik_constraints: vec![],
+            morph_weights: HashMap::new().into(),
+            morph_weight_overrides: HashMap::new(),
+            pose_cache: None,
+            cached_pose: RefCell::new(None),
+        }
+    }
+
+    pub fn animation_count(&self) -> usize {
+        self.animations.len()
+    }
+
+    pub fn animation_names(&self) -> Vec<Rc<str>> {
+        self.animations.iter().map(|animation| animation.name.clone()).collect()
+    }
+
+    pub fn get_animation(&self, index: usize) -> Option<&Rc<ModelAnimation>> {
+        self.animations.get(index)
+    }
+
+    pub fn get_animation_index(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name.as_ref() == name)
+    }
+
+    pub fn get_animation_by_name(&self, name: &str) -> Option<&Rc<ModelAnimation>> {
+        self.animations.iter().find(|animation| animation.name.as_ref() == name)
+    }
+
+    /// Returns a clip covering the whole of the named animation.
+    pub fn get_clip(&self, name: &str, repeat: AnimationRepeat) -> Option<Rc<AnimationClip>> {
+        let index = self.get_animation_index(name)?;
+        let clip = AnimationClip::new(0.0, self.animations[index].duration, repeat).with_animation(index);
+        Some(Rc::new(clip))
+    }
+
+    /// The animation at `index`, falling back to the first one for an out of range index.
+    pub(crate) fn animation(&self, index: usize) -> &Rc<ModelAnimation> {
+        self.animations.get(index).unwrap_or(&self.animations[0])
+    }
+
+    /// The channel map of the animation at `index`, with the same fallback as `animation`. The map is
+    /// rebuilt for animations pushed to `animations` without `add_animation`.
+    fn channels(&self, index: usize) -> Rc<[Option<usize>]> {
+        let index = if index < self.animations.len() { index } else { 0 };
+
+        match self.animation_channels.get(index) {
+            Some(channels) if channels.len() == self.pose_nodes.len() => channels.clone(),
+            _ => channel_map(&self.pose_nodes, &self.animations[index]),
         }
     }
 
     pub fn play_clip(&mut self, clip: &Rc<AnimationClip>) {
+        self.held_pose = None;
         self.current_animation = PlayingAnimation {
             animation_clip: clip.clone(),
             current_tick: -1.0,
-            ticks_per_second: self.model_animation.ticks_per_second,
+            ticks_per_second: self.animation(clip.animation_index).ticks_per_second,
             repeat_completions: 0,
         }
     }
 
-    pub fn play_weight_animations(&mut self, weighted_animation: &[WeightedAnimation], frame_time: f32) {
-        {
-            let mut node_map = self.node_transforms.borrow_mut();
-            let node_animations = self.model_animation.node_animations.borrow();
+    /// The local pose of the last update, with the morph target weights, to blend from or hold later.
+    pub fn capture_pose(&self) -> CapturedPose {
+        let (local_transforms, morph_weights) = match self.cached_pose.borrow().as_ref() {
+            Some(cached_pose) => (cached_pose.local_pose.clone(), cached_pose.morph_weights.clone()),
+            None => (self.local_pose.borrow().clone(), self.morph_weights.borrow().clone()),
+        };
 
-            // reset node transforms
-            node_map.clear();
+        CapturedPose {
+            node_names: self.pose_nodes.iter().map(|pose_node| pose_node.name.clone()).collect(),
+            local_transforms,
+            morph_weights,
+        }
+    }
 
-            let inverse_transform = Transform::from_matrix(self.global_inverse_transform);
+    /// Fades from the pose into what is playing over the duration, such as from the pose a character was in
+    /// when it died into its death clip. Replaces the transitions, which are part of a pose captured from this animator.
+    pub fn blend_from_pose(&mut self, pose: &CapturedPose, duration: Duration) {
+        self.transitions.borrow_mut().clear();
+        self.pose_transition = Some(PoseTransition {
+            current_weight: 1.0,
+            weight_decline_per_sec: 1.0 / duration.as_secs_f32(),
+            pose: Rc::new(pose.clone()),
+        });
+    }
 
-            for weighted in weighted_animation {
-                if weighted.weight == 0.0 {
-                    continue;
-                }
+    /// Holds the pose instead of the current animation, which is paused until a clip is played.
+    /// With `blend_from_pose` it blends a captured pose into a procedural one.
+    pub fn hold_pose(&mut self, pose: &CapturedPose) {
+        self.held_pose = Some(Rc::new(pose.clone()));
+    }
 
-                let tick_range = weighted.end_tick - weighted.start_tick;
+    /// Poses the model from the animations blended by their weights, which are normalized so they don't need to sum to 1.
+    /// The root motion of each animation since the last call is blended by the same weights.
+    pub fn play_weight_animations(&mut self, weighted_animation: &[WeightedAnimation], frame_time: f32) {
+        let mut pose = PoseBlend::new(self.pose_nodes.len());
+        let mut motion = PoseBlend::new(1);
+        let mut weighted_ticks = HashMap::new();
 
-                let mut target_anim_ticks = if weighted.optional_start > 0.0 {
-                    let tick = (frame_time - weighted.optional_start) * self.model_animation.ticks_per_second + weighted.offset;
-                    min(tick, tick_range)
-                } else {
-                    (frame_time * self.model_animation.ticks_per_second + weighted.offset) % tick_range
-                };
+        for weighted in weighted_animation {
+            if weighted.weight <= 0.0 {
+                continue;
+            }
 
-                target_anim_ticks += weighted.start_tick;
+            let model_animation = self.animation(weighted.animation_index);
 
-                if target_anim_ticks < (weighted.start_tick - 0.01) || target_anim_ticks > (weighted.end_tick + 0.01) {
-                    panic!("target_anim_ticks out of range: {}", target_anim_ticks);
-                }
+            let tick_range = weighted.end_tick - weighted.start_tick;
 
-                calculate_transform_maps(
-                    &self.root_node,
-                    &node_animations,
-                    &mut node_map,
-                    inverse_transform,
-                    target_anim_ticks,
-                    weighted.weight,
-                );
+            let mut target_anim_ticks = if weighted.optional_start > 0.0 {
+                let tick = (frame_time - weighted.optional_start) * model_animation.ticks_per_second + weighted.offset;
+                min(tick, tick_range)
+            } else {
+                (frame_time * model_animation.ticks_per_second + weighted.offset) % tick_range
+            };
+
+            target_anim_ticks += weighted.start_tick;
+
+            if target_anim_ticks < (weighted.start_tick - 0.01) || target_anim_ticks > (weighted.end_tick + 0.01) {
+                panic!("target_anim_ticks out of range: {}", target_anim_ticks);
+            }
+
+            let key = (weighted.animation_index, weighted.start_tick.to_bits(), weighted.end_tick.to_bits());
+            if let Some(from_tick) = self.weighted_ticks.get(&key) {
+                let weighted_motion = self.weighted_root_motion(weighted, *from_tick, target_anim_ticks);
+                motion.add(0, weighted_motion, weighted.weight);
             }
+            weighted_ticks.insert(key, target_anim_ticks);
+
+            self.sample_animation(
+                &mut pose,
+                weighted.animation_index,
+                target_anim_ticks,
+                weighted.start_tick,
+                weighted.weight,
+            );
         }
 
+        self.weighted_ticks = weighted_ticks;
+        self.accumulate_root_motion(motion.get(0).unwrap_or(Transform::IDENTITY));
+
+        let mut local_pose = self.finish_pose(&pose);
+        self.update_morph_weights(&pose);
+        self.solve_ik_constraints(&mut local_pose);
+        self.update_node_transforms(&local_pose);
         self.update_final_transforms();
     }
 
+    /// Fades out what was playing over the duration. A held pose fades out as the pose transition, with
+    /// a pose transition still fading out under it folded in.
     pub fn play_clip_with_transition(&mut self, clip: &Rc<AnimationClip>, transition_duration: Duration) {
+        if let Some(held_pose) = self.held_pose.take() {
+            let pose = match self.pose_transition.take() {
+                Some(pose_transition) => Rc::new(self.fold_pose_transition(&pose_transition, &held_pose)),
+                None => held_pose,
+            };
+
+            self.play_clip(clip);
+            self.pose_transition = Some(PoseTransition {
+                current_weight: 1.0,
+                weight_decline_per_sec: 1.0 / transition_duration.as_secs_f32(),
+                pose,
+            });
+            return;
+        }
+
         let mut animation = PlayingAnimation {
             animation_clip: clip.clone(),
             current_tick: -1.0,
-            ticks_per_second: self.model_animation.ticks_per_second,
+            ticks_per_second: self.animation(clip.animation_index).ticks_per_second,
             repeat_completions: 0,
         };
 
@@ -243,128 +616,1457 @@ impl Animator {
     }
 
     pub fn update_animation(&mut self, delta_time: f32) {
-        self.current_animation.update(delta_time);
+        self.weighted_ticks.clear();
+
+        let root_motion = match self.held_pose {
+            Some(_) => Transform::IDENTITY,
+            None => self.advance_current_animation(delta_time),
+        };
+
         self.update_transitions(delta_time);
-        self.update_node_map(delta_time);
+
+        if let Some(pose_cache) = self.shared_pose_cache() {
+            self.accumulate_root_motion(root_motion);
+            self.update_from_pose_cache(&pose_cache);
+            return;
+        }
+
+        let (mut local_pose, root_motion) = self.blend_playing_animations(delta_time, root_motion);
+        self.accumulate_root_motion(root_motion);
+
+        self.update_layers(delta_time, &mut local_pose);
+        self.solve_ik_constraints(&mut local_pose);
+        self.update_node_transforms(&local_pose);
+        self.update_final_transforms();
+    }
+
+    /// Advances the current animation, firing its events and completing its clip. Returns its root motion.
+    fn advance_current_animation(&mut self, delta_time: f32) -> Transform {
+        let was_finished = self.current_animation.is_finished();
+        let from_tick = self.current_animation.current_tick;
+        let from_completions = self.current_animation.repeat_completions;
+
+        let mut events = vec![];
+        self.current_animation.update(delta_time, &mut events);
+        self.push_fired_events(events, &self.current_animation.animation_clip, 1.0);
+
+        let root_motion = self.root_motion_delta(&self.current_animation, from_tick, from_completions);
+
+        if !was_finished && self.current_animation.is_finished() {
+            self.complete_current_clip();
+        }
+
+        root_motion
+    }
+
+    fn accumulate_root_motion(&self, root_motion: Transform) {
+        if self.root_motion_node.is_some() {
+            let mut accumulated = self.root_motion.borrow_mut();
+            *accumulated = add_root_motion(*accumulated, root_motion);
+        }
+    }
+
+    /// Shares sampled poses through the cache with the animators of other models of the same skeleton.
+    pub fn set_pose_cache(&mut self, pose_cache: &Rc<PoseCache>) {
+        self.pose_cache = Some(pose_cache.clone());
+    }
+
+    pub fn clear_pose_cache(&mut self) {
+        self.pose_cache = None;
+    }
+
+    /// The pose cache, when the pose is the current clip alone and so can be shared.
+    fn shared_pose_cache(&self) -> Option<Rc<PoseCache>> {
+        let shareable = self.transitions.borrow().is_empty()
+            && self.pose_transition.is_none()
+            && self.held_pose.is_none()
+            && self.layers.is_empty()
+            && self.ik_constraints.is_empty();
+        self.pose_cache.clone().filter(|_| shareable)
+    }
+
+    /// Poses the model from the cache, sampling the current clip into it when the pose isn't there yet.
+    fn update_from_pose_cache(&self, pose_cache: &PoseCache) {
+        let playing = &self.current_animation;
+        let clip = &playing.animation_clip;
+        let animation = self.animation(clip.animation_index);
+        let (sample, tick) = pose_cache.quantize(playing.current_tick, animation.ticks_per_second);
+
+        let key = PoseKey {
+            skeleton_id: self.skeleton_id,
+            animation_id: animation_id(animation),
+            sample,
+            root_motion: self.root_motion_node.clone().map(|name| (name, clip.start_tick.to_bits())),
+            skinning_method: self.skinning_method,
+        };
+
+        if let Some(cached_pose) = pose_cache.get(&key) {
+            self.final_bone_matrices
+                .borrow_mut()
+                .copy_from_slice(&cached_pose.final_bone_matrices);
+            self.final_bone_dual_quats
+                .borrow_mut()
+                .copy_from_slice(&cached_pose.final_bone_dual_quats);
+            self.final_node_matrices
+                .borrow_mut()
+                .copy_from_slice(&cached_pose.final_node_matrices);
+            *self.cached_pose.borrow_mut() = Some(cached_pose);
+            return;
+        }
+
+        let mut pose = PoseBlend::new(self.pose_nodes.len());
+        self.sample_animation(&mut pose, clip.animation_index, tick, clip.start_tick, 1.0);
+        self.update_morph_weights(&pose);
+        self.update_node_transforms(&self.finish_pose(&pose));
         self.update_final_transforms();
+
+        pose_cache.insert(
+            key,
+            CachedPose {
+                animation: animation.clone(),
+                local_pose: self.local_pose.borrow().clone(),
+                node_transforms: self.node_transforms.borrow().clone(),
+                final_bone_matrices: self.final_bone_matrices.borrow().clone(),
+                final_bone_dual_quats: self.final_bone_dual_quats.borrow().clone(),
+                final_node_matrices: self.final_node_matrices.borrow().clone(),
+                morph_weights: self.morph_weights.borrow().clone(),
+            },
+        );
+    }
+
+    /// The held pose with the pose transition over it at its current weight, the pose they blend to.
+    fn fold_pose_transition(&self, pose_transition: &PoseTransition, held_pose: &CapturedPose) -> CapturedPose {
+        let weight = pose_transition.current_weight.clamp(0.0, 1.0);
+
+        let mut pose = PoseBlend::new(self.pose_nodes.len());
+        pose_transition.pose.add_to_blend(&self.pose_nodes, &mut pose, weight);
+        held_pose.add_to_blend(&self.pose_nodes, &mut pose, 1.0 - weight);
+
+        CapturedPose {
+            node_names: self.pose_nodes.iter().map(|pose_node| pose_node.name.clone()).collect(),
+            local_transforms: self.finish_pose(&pose),
+            morph_weights: self.blended_morph_weights(&pose),
+        }
     }
 
     fn update_transitions(&mut self, delta_time: f32) {
         self.transitions.borrow_mut().retain_mut(|animation| {
             animation.current_weight -= animation.weight_decline_per_sec * delta_time;
             animation.current_weight > 0.0
-        })
-    }
+        });
 
-    fn update_node_map(&mut self, delta_time: f32) {
-        self.node_transforms.borrow_mut().clear();
+        if let Some(pose_transition) = &mut self.pose_transition {
+            pose_transition.current_weight -= pose_transition.weight_decline_per_sec * delta_time;
+            if pose_transition.current_weight <= 0.0 {
+                self.pose_transition = None;
+            }
+        }
+    }
 
+    /// Advances the transitions and samples them with the current animation into a local pose. Each transition
+    /// fades out the animations that were playing before it, the pose transition is the oldest, and the current
+    /// animation, or the held pose, gets the weight that is left.
+    /// Returns the pose and `root_motion`, the current animation's root motion, blended with the transitions the same way.
+    fn blend_playing_animations(&self, delta_time: f32, root_motion: Transform) -> (Vec<Transform>, Transform) {
         let mut transitions = self.transitions.borrow_mut();
-        let mut node_map = self.node_transforms.borrow_mut();
-        let node_animations = self.model_animation.node_animations.borrow();
 
-        let inverse_transform = Transform::from_matrix(self.global_inverse_transform);
-
-        // First for current animation at weight 1.0
-        calculate_transform_maps(
-            &self.root_node,
-            &node_animations,
-            &mut node_map,
-            inverse_transform,
-            self.current_animation.current_tick,
-            1.0,
-        );
+        let mut transition_motions = Vec::with_capacity(transitions.len());
 
         for transition in transitions.iter_mut() {
-            transition.animation.update(delta_time);
-            calculate_transform_maps(
-                &self.root_node,
-                &node_animations,
-                &mut node_map,
-                inverse_transform,
-                transition.animation.current_tick,
-                transition.current_weight,
-            );
+            let from_tick = transition.animation.current_tick;
+            let from_completions = transition.animation.repeat_completions;
+
+            let mut events = vec![];
+            transition.animation.update(delta_time, &mut events);
+            self.push_fired_events(events, &transition.animation.animation_clip, transition.current_weight);
+
+            transition_motions.push(self.root_motion_delta(&transition.animation, from_tick, from_completions));
+        }
+
+        let mut pose = PoseBlend::new(self.pose_nodes.len());
+        let mut motion = PoseBlend::new(1);
+        let mut remaining_weight = 1.0;
+
+        for (transition, transition_motion) in transitions.iter().zip(transition_motions).rev() {
+            let transition_weight = transition.current_weight.clamp(0.0, 1.0);
+            let weight = transition_weight * remaining_weight;
+            remaining_weight *= 1.0 - transition_weight;
+
+            let playing = &transition.animation;
+            let clip = &playing.animation_clip;
+            self.sample_animation(&mut pose, clip.animation_index, playing.current_tick, clip.start_tick, weight);
+            motion.add(0, transition_motion, weight);
+        }
+
+        if let Some(pose_transition) = &self.pose_transition {
+            let transition_weight = pose_transition.current_weight.clamp(0.0, 1.0);
+            let weight = transition_weight * remaining_weight;
+            remaining_weight *= 1.0 - transition_weight;
+
+            pose_transition.pose.add_to_blend(&self.pose_nodes, &mut pose, weight);
+            motion.add(0, Transform::IDENTITY, weight);
+        }
+
+        match &self.held_pose {
+            Some(held_pose) => held_pose.add_to_blend(&self.pose_nodes, &mut pose, remaining_weight),
+            None => {
+                let playing = &self.current_animation;
+                let clip = &playing.animation_clip;
+                self.sample_animation(
+                    &mut pose,
+                    clip.animation_index,
+                    playing.current_tick,
+                    clip.start_tick,
+                    remaining_weight,
+                );
+            }
         }
+        motion.add(0, root_motion, remaining_weight);
+
+        self.update_morph_weights(&pose);
+
+        (self.finish_pose(&pose), motion.get(0).unwrap_or(Transform::IDENTITY))
     }
 
-    fn update_final_transforms(&self) {
-        let bone_data_map = self.bone_data_map.borrow();
+    /// Adds the local transforms of every node at `tick` to the pose. `start_tick`, the start of the clip,
+    /// is the pose the root motion node is held at.
+    fn sample_animation(&self, pose: &mut PoseBlend, animation_index: usize, tick: f32, start_tick: f32, weight: f32) {
+        if weight <= 0.0 {
+            return;
+        }
 
-        let mut final_bones = self.final_bone_matrices.borrow_mut();
-        let mut final_node = self.final_node_matrices.borrow_mut();
+        let node_animations = self.animation(animation_index).node_animations.borrow();
+        let channels = self.channels(animation_index);
+        let root_motion = self.root_motion_node.as_deref().map(|name| (name, start_tick));
 
-        for (node_name, node_transform) in self.node_transforms.borrow_mut().iter() {
-            if let Some(bone_data) = bone_data_map.get(node_name.deref()) {
-                let index = bone_data.bone_index as usize;
-                let transform_matrix = node_transform.transform.mul_transform(bone_data.offset_transform).compute_matrix();
-                final_bones[index] = transform_matrix;
+        for (index, pose_node) in self.pose_nodes.iter().enumerate() {
+            let node_animation = channels[index].map(|channel| &node_animations[channel]);
+            pose.add(index, local_transform(pose_node, node_animation, tick, root_motion), weight);
+
+            if let Some(morph_weights) = node_animation.and_then(|node_animation| node_animation.get_morph_weights(tick)) {
+                pose.add_morph_weights(index, &morph_weights, weight);
             }
+        }
+    }
 
-            for mesh_index in node_transform.meshes.iter() {
-                final_node[*mesh_index as usize] = node_transform.transform.compute_matrix();
+    /// The bone and node palettes of the animation at `tick`, without changing the animator's pose.
+    pub fn sample_palettes(&self, animation_index: usize, tick: f32) -> (Vec<Mat4>, Vec<Mat4>) {
+        let mut pose = PoseBlend::new(self.pose_nodes.len());
+        self.sample_animation(&mut pose, animation_index, tick, 0.0, 1.0);
+
+        let inverse_transform = Transform::from_matrix(self.global_inverse_transform);
+        let global_transforms = global_pose(&self.pose_nodes, &self.finish_pose(&pose), inverse_transform);
+
+        let bone_data_map = self.bone_data_map.borrow();
+        let mut bones = vec![Mat4::IDENTITY; self.final_bone_matrices.borrow().len()];
+        let mut nodes = vec![Mat4::IDENTITY; self.final_node_matrices.borrow().len()];
+
+        for (pose_node, global_transform) in self.pose_nodes.iter().zip(global_transforms) {
+            if let Some(bone_data) = bone_data_map.get(pose_node.name.deref()) {
+                if let Some(bone) = bones.get_mut(bone_data.bone_index as usize) {
+                    *bone = global_transform.mul_transform(bone_data.offset_transform).compute_matrix();
+                }
+            }
+
+            for mesh_index in pose_node.meshes.iter() {
+                if let Some(node) = nodes.get_mut(*mesh_index as usize) {
+                    *node = global_transform.compute_matrix();
+                }
             }
         }
+
+        (bones, nodes)
     }
-}
 
-/// Converts scene Node tree to local NodeData tree. Converting all the transforms to column major form.
-fn read_hierarchy_data(source: &Rc<Node>) -> NodeData {
-    let mut node_data = NodeData {
-        name: Rc::from(source.name.as_str()),
-        transform: Transform::from_matrix(source.transformation),
-        children: vec![],
-        meshes: Rc::from(source.meshes.clone()),
-    };
+    /// The blended local pose, nodes without samples are at rest.
+    fn finish_pose(&self, pose: &PoseBlend) -> Vec<Transform> {
+        self.pose_nodes
+            .iter()
+            .enumerate()
+            .map(|(index, pose_node)| pose.get(index).unwrap_or(pose_node.transform))
+            .collect()
+    }
 
-    // debug!("NodeData: {} meshes: {:?}", &node_data.name, &source.meshes);
+    /// Keeps the blended morph target weights of the nodes the animations set them for.
+    fn update_morph_weights(&self, pose: &PoseBlend) {
+        *self.morph_weights.borrow_mut() = self.blended_morph_weights(pose);
+    }
 
-    for child in source.children.borrow().iter() {
-        let node = read_hierarchy_data(child);
-        node_data.children.push(node);
+    fn blended_morph_weights(&self, pose: &PoseBlend) -> HashMap<Rc<str>, Vec<f32>> {
+        let mut morph_weights = HashMap::new();
+
+        for (index, pose_node) in self.pose_nodes.iter().enumerate() {
+            if let Some(weights) = pose.get_morph_weights(index) {
+                morph_weights.insert(pose_node.name.clone(), weights);
+            }
+        }
+
+        morph_weights
     }
-    node_data
-}
 
-pub fn calculate_transform_maps(
-    node_data: &NodeData,
-    node_animations: &Ref<Vec<NodeAnimation>>,
-    node_map: &mut RefMut<HashMap<Rc<str>, NodeTransform>>,
-    parent_transform: Transform,
-    current_tick: f32,
-    weight: f32,
-) {
-    let global_transformation = calculate_transform(node_data, node_animations, node_map, parent_transform, current_tick, weight);
+    /// Sets the morph target weights of the node's meshes, overriding any animation of them.
+    pub fn set_morph_weights(&mut self, node_name: &str, weights: &[f32]) {
+        self.morph_weight_overrides.insert(Rc::from(node_name), weights.to_vec());
+    }
 
-    for child_node in node_data.children.iter() {
-        calculate_transform_maps(child_node, node_animations, node_map, global_transformation, current_tick, weight);
+    /// Hands the node's morph target weights back to the animations.
+    pub fn clear_morph_weights(&mut self, node_name: &str) {
+        self.morph_weight_overrides.remove(node_name);
     }
-}
 
-fn calculate_transform(
-    node_data: &NodeData,
-    node_animations: &Ref<Vec<NodeAnimation>>,
-    node_map: &mut RefMut<HashMap<Rc<str>, NodeTransform>>,
-    parent_transform: Transform,
-    current_tick: f32,
-    weight: f32,
-) -> Transform {
-    let some_node_animation = node_animations.iter().find(|node_anim| node_anim.name == node_data.name);
+    /// The morph target weights of the mesh from code or the animations, None when neither sets them.
+    pub fn mesh_morph_weights(&self, mesh_id: u32) -> Option<Vec<f32>> {
+        let pose_node = self.pose_nodes.iter().find(|pose_node| pose_node.meshes.contains(&mesh_id))?;
 
-    let global_transform = match some_node_animation {
-        Some(node_animation) => {
-            let node_transform = node_animation.get_animation_transform(current_tick);
-            parent_transform.mul_transform(node_transform)
+        if let Some(weights) = self.morph_weight_overrides.get(&pose_node.name) {
+            return Some(weights.clone());
         }
-        None => parent_transform.mul_transform(node_data.transform),
-    };
 
-    node_map
-        .entry_ref(node_data.name.as_ref())
-        .and_modify(|n| {
-            n.transform = n.transform.mul_transform_weighted(global_transform, weight);
-        })
-        .or_insert(NodeTransform::new(global_transform, &node_data.meshes));
+        match self.cached_pose.borrow().as_ref() {
+            Some(cached_pose) => cached_pose.morph_weights.get(&pose_node.name).cloned(),
+            None => self.morph_weights.borrow().get(&pose_node.name).cloned(),
+        }
+    }
+
+    /// The node's transform in the model's space from the last update.
+    pub fn get_node_transform(&self, node_name: &str) -> Option<NodeTransform> {
+        match self.cached_pose.borrow().as_ref() {
+            Some(cached_pose) => cached_pose.node_transforms.get(node_name).cloned(),
+            None => self.node_transforms.borrow().get(node_name).cloned(),
+        }
+    }
+
+    /// Computes the global node transforms from the local pose, and keeps the local pose for `capture_pose`.
+    fn update_node_transforms(&self, local_pose: &[Transform]) {
+        self.cached_pose.borrow_mut().take();
+        local_pose.clone_into(&mut self.local_pose.borrow_mut());
+
+        let inverse_transform = Transform::from_matrix(self.global_inverse_transform);
+        let global_transforms = global_pose(&self.pose_nodes, local_pose, inverse_transform);
+
+        let mut node_map = self.node_transforms.borrow_mut();
+        node_map.clear();
+
+        for (pose_node, global_transform) in self.pose_nodes.iter().zip(global_transforms) {
+            node_map.insert(pose_node.name.clone(), NodeTransform::new(global_transform, &pose_node.meshes));
+        }
+    }
+
+    /// Adds a layer, or replaces the layer with the same name, playing the clip from its start.
+    pub fn set_layer(&mut self, name: &str, clip: &Rc<AnimationClip>, weight: f32, mask: BoneMask) {
+        self.insert_layer(name, clip, weight, mask, LayerBlend::Override);
+    }
+
+    /// Adds or replaces a layer that applies a clip of an additive animation on top of the layers
+    /// before it, such as recoil or breathing.
+    pub fn set_additive_layer(&mut self, name: &str, clip: &Rc<AnimationClip>, weight: f32, mask: BoneMask) {
+        self.insert_layer(name, clip, weight, mask, LayerBlend::Additive);
+    }
+
+    fn insert_layer(&mut self, name: &str, clip: &Rc<AnimationClip>, weight: f32, mask: BoneMask, blend: LayerBlend) {
+        let layer = AnimationLayer {
+            name: Rc::from(name),
+            animation: PlayingAnimation {
+                animation_clip: clip.clone(),
+                current_tick: -1.0,
+                ticks_per_second: self.animation(clip.animation_index).ticks_per_second,
+                repeat_completions: 0,
+            },
+            weight,
+            mask,
+            blend,
+        };
 
-    global_transform
+        match self.layers.iter_mut().find(|l| l.name == layer.name) {
+            Some(existing) => *existing = layer,
+            None => self.layers.push(layer),
+        }
+    }
+
+    /// The bind pose of the model's skeleton, for retargeting animations to or from it.
+    pub fn bind_pose(&self) -> BindPose {
+        BindPose::new(&self.root_node, &self.bone_data_map.borrow())
+    }
+
+    /// Adds an animation, such as one retargeted from another skeleton. Returns its index.
+    pub fn add_animation(&mut self, animation: ModelAnimation) -> usize {
+        self.animation_channels.push(channel_map(&self.pose_nodes, &animation));
+        self.animations.push(Rc::new(animation));
+        self.animations.len() - 1
+    }
+
+    /// Adds an additive version of the animation at `source_index`, named `name`, for additive layers.
+    /// Every key becomes its difference from the animation's pose at `reference_tick`. Returns the new animation's index.
+    pub fn add_additive_animation(&mut self, name: &str, source_index: usize, reference_tick: f32) -> usize {
+        let additive = self.animation(source_index).to_additive(name, reference_tick);
+        self.add_animation(additive)
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&AnimationLayer> {
+        self.layers.iter().find(|l| l.name.as_ref() == name)
+    }
+
+    /// Sets the weight of the named layer, 0.0 turns it off.
+    pub fn set_layer_weight(&mut self, name: &str, weight: f32) {
+        if let Some(layer) = self.layers.iter_mut().find(|l| l.name.as_ref() == name) {
+            layer.weight = weight;
+        }
+    }
+
+    pub fn remove_layer(&mut self, name: &str) {
+        self.layers.retain(|l| l.name.as_ref() != name);
+    }
+
+    /// Applies the layers in order to the masked nodes of the local pose.
+    fn update_layers(&mut self, delta_time: f32, local_pose: &mut [Transform]) {
+        let mut layers = std::mem::take(&mut self.layers);
+
+        for layer in layers.iter_mut() {
+            let mut events = vec![];
+            layer.animation.update(delta_time, &mut events);
+            self.push_fired_events(events, &layer.animation.animation_clip, layer.weight);
+
+            if layer.weight <= 0.0 {
+                continue;
+            }
+
+            let clip = &layer.animation.animation_clip;
+            let node_animations = self.animation(clip.animation_index).node_animations.borrow();
+            let channels = self.channels(clip.animation_index);
+            let root_motion = self.root_motion_node.as_deref().map(|name| (name, clip.start_tick));
+
+            let mut masked = vec![false; self.pose_nodes.len()];
+
+            for (index, pose_node) in self.pose_nodes.iter().enumerate() {
+                let parent_masked = pose_node.parent.is_some_and(|parent| masked[parent]);
+                masked[index] = layer.mask.contains(&pose_node.name, parent_masked);
+
+                if !masked[index] {
+                    continue;
+                }
+
+                let local = local_pose[index];
+                let node_animation = channels[index].map(|channel| &node_animations[channel]);
+
+                local_pose[index] = match layer.blend {
+                    LayerBlend::Override => {
+                        let layer_local = local_transform(pose_node, node_animation, layer.animation.current_tick, root_motion);
+                        local.mul_transform_weighted(layer_local, layer.weight.min(1.0))
+                    }
+                    // additive deltas can be scaled past 1.0, and have no root motion to hold
+                    LayerBlend::Additive => match node_animation {
+                        Some(node_animation) => add_weighted_delta(
+                            local,
+                            node_animation.get_animation_transform(layer.animation.current_tick),
+                            layer.weight,
+                        ),
+                        None => local,
+                    },
+                };
+            }
+        }
+
+        self.layers = layers;
+    }
+
+    /// Adds an IK constraint, or replaces the constraint with the same name. Targets are in the model's space,
+    /// the space of the node transforms.
+    pub fn set_ik_constraint(&mut self, name: &str, constraint: IkConstraint) {
+        match self.ik_constraints.iter_mut().find(|(n, _)| n.as_ref() == name) {
+            Some((_, existing)) => *existing = constraint,
+            None => self.ik_constraints.push((Rc::from(name), constraint)),
+        }
+    }
+
+    /// For moving the target of a constraint each frame.
+    pub fn get_ik_constraint_mut(&mut self, name: &str) -> Option<&mut IkConstraint> {
+        self.ik_constraints
+            .iter_mut()
+            .find(|(n, _)| n.as_ref() == name)
+            .map(|(_, constraint)| constraint)
+    }
+
+    pub fn remove_ik_constraint(&mut self, name: &str) {
+        self.ik_constraints.retain(|(n, _)| n.as_ref() != name);
+    }
+
+    fn solve_ik_constraints(&self, local_pose: &mut [Transform]) {
+        let inverse_transform = Transform::from_matrix(self.global_inverse_transform);
+
+        for (_, constraint) in self.ik_constraints.iter() {
+            constraint.solve(&self.pose_nodes, local_pose, inverse_transform);
+        }
+    }
+
+    /// Takes the translation and rotation of the node out of the pose, so that the caller can move the model
+    /// by the animated motion instead. The node is held at its pose at the start of the clip.
+    pub fn set_root_motion_node(&mut self, node_name: &str) {
+        self.root_motion_node = Some(Rc::from(node_name));
+    }
+
+    pub fn clear_root_motion_node(&mut self) {
+        self.root_motion_node = None;
+    }
+
+    /// Returns the root motion since the last call, in the space of the root motion node's parent.
+    /// The translation is the distance moved and the rotation the turn made.
+    pub fn take_root_motion(&self) -> Transform {
+        std::mem::replace(&mut *self.root_motion.borrow_mut(), Transform::IDENTITY)
+    }
+
+    /// The root node motion of `playing` since it was at `from_tick` with `from_completions`, including
+    /// the motion of every loop it wrapped around.
+    fn root_motion_delta(&self, playing: &PlayingAnimation, from_tick: f32, from_completions: u32) -> Transform {
+        let animation_index = playing.animation_clip.animation_index;
+        let Some(channel) = self.root_motion_channel(animation_index) else {
+            return Transform::IDENTITY;
+        };
+
+        let node_animations = self.animation(animation_index).node_animations.borrow();
+        let node_animation = &node_animations[channel];
+
+        let clip = &playing.animation_clip;
+        let from_tick = if from_tick < 0.0 { clip.start_tick } else { from_tick };
+
+        // the completion that finishes a clip stops at its end instead of wrapping
+        let mut wraps = playing.repeat_completions - from_completions;
+        if wraps > 0 && playing.is_finished() {
+            wraps -= 1;
+        }
+
+        if wraps == 0 {
+            return root_motion_between(node_animation, from_tick, playing.current_tick);
+        }
+
+        let full_loop = root_motion_between(node_animation, clip.start_tick, clip.end_tick);
+
+        let mut motion = root_motion_between(node_animation, from_tick, clip.end_tick);
+        for _ in 1..wraps {
+            motion = add_root_motion(motion, full_loop);
+        }
+        add_root_motion(motion, root_motion_between(node_animation, clip.start_tick, playing.current_tick))
+    }
+
+    /// The root node motion of a weighted animation sampled at `from_tick` and then at `to_tick`. A looping
+    /// animation that went back wrapped around its end, one that doesn't loop started over.
+    fn weighted_root_motion(&self, weighted: &WeightedAnimation, from_tick: f32, to_tick: f32) -> Transform {
+        let Some(channel) = self.root_motion_channel(weighted.animation_index) else {
+            return Transform::IDENTITY;
+        };
+
+        let node_animations = self.animation(weighted.animation_index).node_animations.borrow();
+        let node_animation = &node_animations[channel];
+
+        if to_tick >= from_tick {
+            return root_motion_between(node_animation, from_tick, to_tick);
+        }
+
+        if weighted.optional_start > 0.0 {
+            return root_motion_between(node_animation, weighted.start_tick, to_tick);
+        }
+
+        add_root_motion(
+            root_motion_between(node_animation, from_tick, weighted.end_tick),
+            root_motion_between(node_animation, weighted.start_tick, to_tick),
+        )
+    }
+
+    /// The channel of the root motion node in the animation at `animation_index`.
+    fn root_motion_channel(&self, animation_index: usize) -> Option<usize> {
+        let root_motion_node = self.root_motion_node.as_ref()?;
+        let index = self.pose_nodes.iter().position(|pose_node| pose_node.name == *root_motion_node)?;
+        self.channels(animation_index)[index]
+    }
+
+    /// Sets the clip to play when a one shot or counted clip finishes, such as going back to idle after a reload.
+    pub fn set_fallback_clip(&mut self, clip: &Rc<AnimationClip>, transition_duration: Duration) {
+        self.fallback_clip = Some((clip.clone(), transition_duration));
+    }
+
+    /// Finished clips hold their last frame.
+    pub fn clear_fallback_clip(&mut self) {
+        self.fallback_clip = None;
+    }
+
+    pub fn is_current_clip_finished(&self) -> bool {
+        self.current_animation.is_finished()
+    }
+
+    /// Returns the clips that finished since the last call, in the order they finished.
+    pub fn drain_completed_clips(&self) -> Vec<Rc<AnimationClip>> {
+        self.completed_clips.borrow_mut().drain(..).collect()
+    }
+
+    fn complete_current_clip(&mut self) {
+        let clip = self.current_animation.animation_clip.clone();
+        self.completed_clips.borrow_mut().push(clip.clone());
+
+        if let Some((fallback_clip, transition_duration)) = self.fallback_clip.clone() {
+            // a fallback clip that finishes holds its last frame rather than restarting
+            if Rc::ptr_eq(&fallback_clip, &clip) {
+                return;
+            }
+            if transition_duration.is_zero() {
+                self.play_clip(&fallback_clip);
+            } else {
+                self.play_clip_with_transition(&fallback_clip, transition_duration);
+            }
+
+            // start the fallback clip so this update poses it at its first tick
+            let mut events = vec![];
+            self.current_animation.update(0.0, &mut events);
+            self.push_fired_events(events, &fallback_clip, 1.0);
+        }
+    }
+
+    /// Returns the events fired since the last call, in the order they fired.
+    pub fn drain_events(&self) -> Vec<FiredAnimationEvent> {
+        self.fired_events.borrow_mut().drain(..).collect()
+    }
+
+    fn push_fired_events(&self, events: Vec<AnimationEvent>, animation_clip: &Rc<AnimationClip>, weight: f32) {
+        let mut fired_events = self.fired_events.borrow_mut();
+        for event in events {
+            fired_events.push(FiredAnimationEvent {
+                name: event.name,
+                tick: event.tick,
+                weight,
+                animation_clip: animation_clip.clone(),
+            });
+        }
+    }
+
+    /// Overrides nodes of the last update with transforms computed outside the animator, such as the bones of
+    /// a ragdoll after a physics step, and refills the palettes. The transforms are in the model's space, the same
+    /// as `node_transforms`, and last until the next update. Nodes not listed keep their animated transform,
+    /// including the children of listed ones. The local pose follows, so a pose captured after it blends from them.
+    pub fn apply_bone_transforms(&self, bone_transforms: &[(&str, Transform)]) {
+        // the cached pose is shared, the overrides go on a copy of it
+        if let Some(cached_pose) = self.cached_pose.borrow_mut().take() {
+            self.local_pose.borrow_mut().clone_from(&cached_pose.local_pose);
+            self.node_transforms.borrow_mut().clone_from(&cached_pose.node_transforms);
+            self.morph_weights.borrow_mut().clone_from(&cached_pose.morph_weights);
+        }
+
+        {
+            let mut node_transforms = self.node_transforms.borrow_mut();
+            for (node_name, transform) in bone_transforms {
+                if let Some(node_transform) = node_transforms.get_mut(*node_name) {
+                    node_transform.transform = *transform;
+                }
+            }
+
+            let root_transform = Transform::from_matrix(self.global_inverse_transform);
+            let mut local_pose = self.local_pose.borrow_mut();
+
+            for (pose_node, local) in self.pose_nodes.iter().zip(local_pose.iter_mut()) {
+                let parent_transform = match pose_node.parent {
+                    Some(parent) => node_transforms.get(&self.pose_nodes[parent].name).map(|parent| parent.transform),
+                    None => Some(root_transform),
+                };
+
+                if let (Some(parent_transform), Some(node_transform)) = (parent_transform, node_transforms.get(&pose_node.name)) {
+                    let matrix = parent_transform.compute_matrix().inverse() * node_transform.transform.compute_matrix();
+                    *local = Transform::from_matrix(matrix);
+                }
+            }
+        }
+
+        self.update_final_transforms();
+    }
+
+    /// Fills the palettes, bones and meshes outside of them are skipped.
+    fn update_final_transforms(&self) {
+        let bone_data_map = self.bone_data_map.borrow();
+
+        let mut final_bones = self.final_bone_matrices.borrow_mut();
+        let mut final_dual_quats = self.final_bone_dual_quats.borrow_mut();
+        let mut final_node = self.final_node_matrices.borrow_mut();
+        let dual_quaternion = self.skinning_method == SkinningMethod::DualQuaternion;
+
+        for (node_name, node_transform) in self.node_transforms.borrow_mut().iter() {
+            if let Some(bone_data) = bone_data_map.get(node_name.deref()) {
+                let bone_index = bone_data.bone_index as usize;
+                let bone_transform = node_transform.transform.mul_transform(bone_data.offset_transform);
+
+                if let Some(final_bone) = final_bones.get_mut(bone_index) {
+                    *final_bone = bone_transform.compute_matrix();
+                }
+
+                if dual_quaternion {
+                    if let Some(final_dual_quat) = final_dual_quats.get_mut(bone_index) {
+                        *final_dual_quat = DualQuat::from_transform(&bone_transform);
+                    }
+                }
+            }
+
+            for mesh_index in node_transform.meshes.iter() {
+                if let Some(final_node) = final_node.get_mut(*mesh_index as usize) {
+                    *final_node = node_transform.transform.compute_matrix();
+                }
+            }
+        }
+    }
+}
+
+/// Converts scene Node tree to local NodeData tree. Converting all the transforms to column major form.
+#[cfg(feature = "russimp")]
+fn read_hierarchy_data(source: &Rc<Node>) -> NodeData {
+    let mut node_data = NodeData {
+        name: Rc::from(source.name.as_str()),
+        transform: Transform::from_matrix(source.transformation),
+        children: vec![],
+        meshes: Rc::from(source.meshes.clone()),
+    };
+
+    // debug!("NodeData: {} meshes: {:?}", &node_data.name, &source.meshes);
+
+    for child in source.children.borrow().iter() {
+        let node = read_hierarchy_data(child);
+        node_data.children.push(node);
+    }
+    node_data
+}
+
+/// The index of each pose node's channel in the animation, so sampling doesn't search the channels by name.
+fn channel_map(pose_nodes: &[PoseNode], animation: &ModelAnimation) -> Rc<[Option<usize>]> {
+    let node_animations = animation.node_animations.borrow();
+
+    // reversed so the first of any channels with the same name is kept
+    let channel_indexes: HashMap<&str, usize> = node_animations
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, node_animation)| (node_animation.name.as_ref(), index))
+        .collect();
+
+    pose_nodes
+        .iter()
+        .map(|pose_node| channel_indexes.get(pose_node.name.as_ref()).copied())
+        .collect()
+}
+
+/// The node's transform relative to its parent at `current_tick`, or its rest transform when it isn't animated.
+fn local_transform(
+    pose_node: &PoseNode,
+    node_animation: Option<&NodeAnimation>,
+    current_tick: f32,
+    root_motion: Option<(&str, f32)>,
+) -> Transform {
+    let Some(node_animation) = node_animation else {
+        return pose_node.transform;
+    };
+
+    let mut node_transform = node_animation.get_animation_transform(current_tick);
+
+    if let Some((_, reference_tick)) = root_motion.filter(|(name, _)| *name == pose_node.name.as_ref()) {
+        let reference = node_animation.get_animation_transform(reference_tick);
+        node_transform.translation = reference.translation;
+        node_transform.rotation = reference.rotation;
+    }
+
+    node_transform
+}
+
+/// Applies an additive delta, scaled by `weight`, on top of `base`.
+fn add_weighted_delta(base: Transform, delta: Transform, weight: f32) -> Transform {
+    Transform {
+        translation: base.translation + delta.translation * weight,
+        rotation: (base.rotation * Quat::IDENTITY.slerp(delta.rotation, weight)).normalize(),
+        scale: base.scale * Vec3::ONE.lerp(delta.scale, weight),
+    }
+}
+
+/// The motion of the node from `from_tick` to `to_tick`.
+fn root_motion_between(node_animation: &NodeAnimation, from_tick: f32, to_tick: f32) -> Transform {
+    let from = node_animation.get_animation_transform(from_tick);
+    let to = node_animation.get_animation_transform(to_tick);
+
+    Transform {
+        translation: to.translation - from.translation,
+        rotation: (to.rotation * from.rotation.inverse()).normalize(),
+        scale: Vec3::ONE,
+    }
+}
+
+/// The motion of `first` followed by `second`.
+fn add_root_motion(first: Transform, second: Transform) -> Transform {
+    Transform {
+        translation: first.translation + second.translation,
+        rotation: (second.rotation * first.rotation).normalize(),
+        scale: Vec3::ONE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animator::{AnimationClip, AnimationRepeat, Animator, BoneMask, WeightedAnimation};
+    use crate::blend_space::BlendSpace1D;
+    use crate::dual_quat::DualQuat;
+    use crate::hash_map::HashMap;
+    use crate::model_animation::{BoneData, ModelAnimation, NodeData};
+    use crate::node_animation::{KeyPosition, KeyRotation, KeyScale, KeyWeights, NodeAnimation};
+    use crate::pose_cache::PoseCache;
+    use crate::skinning::SkinningMethod;
+    use crate::transform::Transform;
+    use glam::{Mat4, Quat, Vec3};
+    use std::cell::RefCell;
+    use std::f32::consts::FRAC_PI_2;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    fn moving_node_animation(name: &str, duration: f32, ticks_per_second: f32, end_position: Vec3) -> ModelAnimation {
+        let node_animation = NodeAnimation::from_keys(
+            Rc::from("node"),
+            vec![KeyPosition::new(Vec3::ZERO, 0.0), KeyPosition::new(end_position, duration)],
+            vec![KeyRotation::new(Quat::IDENTITY, 0.0)],
+            vec![KeyScale::new(Vec3::ONE, 0.0)],
+        );
+
+        ModelAnimation {
+            name: Rc::from(name),
+            duration,
+            ticks_per_second,
+            node_animations: RefCell::new(vec![node_animation]),
+        }
+    }
+
+    fn test_animator() -> Animator {
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![NodeData {
+                name: Rc::from("node"),
+                transform: Transform::IDENTITY,
+                children: vec![NodeData {
+                    name: Rc::from("hand"),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    children: vec![],
+                    meshes: Rc::new(vec![]),
+                }],
+                meshes: Rc::new(vec![]),
+            }],
+            meshes: Rc::new(vec![]),
+        };
+
+        let animations = vec![
+            moving_node_animation("idle", 10.0, 10.0, Vec3::X),
+            moving_node_animation("run", 100.0, 50.0, Vec3::Y),
+        ];
+
+        Animator::from_node_data(root_node, animations, RefCell::new(HashMap::new()))
+    }
+
+    #[test]
+    fn test_animations_by_name() {
+        let animator = test_animator();
+
+        assert_eq!(animator.animation_count(), 2);
+        assert_eq!(animator.animation_names(), vec![Rc::from("idle"), Rc::from("run")]);
+        assert_eq!(animator.get_animation_index("run"), Some(1));
+        assert_eq!(animator.get_animation_by_name("run").unwrap().duration, 100.0);
+        assert!(animator.get_clip("walk", AnimationRepeat::Forever).is_none());
+
+        let clip = animator.get_clip("run", AnimationRepeat::Once).unwrap();
+        assert_eq!(clip.animation_index, 1);
+        assert_eq!(clip.end_tick, 100.0);
+    }
+
+    #[test]
+    fn test_play_named_clip() {
+        let mut animator = test_animator();
+
+        let clip = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+        animator.play_clip(&clip);
+        assert_eq!(animator.current_animation.ticks_per_second, 50.0);
+
+        // the first update starts the clip, the second moves it to tick 50, half way
+        animator.update_animation(0.0);
+        animator.update_animation(1.0);
+
+        let node_transforms = animator.node_transforms.borrow();
+        let translation = node_transforms.get("node").unwrap().transform.translation;
+        assert!(translation.abs_diff_eq(Vec3::new(0.0, 0.5, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_empty_animations() {
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![],
+            meshes: Rc::new(vec![]),
+        };
+        let mut animator = Animator::from_node_data(root_node, vec![], RefCell::new(HashMap::new()));
+
+        assert_eq!(animator.animation_count(), 1);
+        animator.update_animation(0.1);
+    }
+
+    fn event_names(animator: &Animator) -> Vec<String> {
+        animator.drain_events().iter().map(|e| e.name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_events_fire_when_crossed() {
+        let mut animator = test_animator();
+
+        // idle runs at 10 ticks per second
+        let clip = Rc::new(
+            AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever)
+                .with_event("start", 0.0)
+                .with_event("left_foot", 2.0)
+                .with_event("right_foot", 7.0),
+        );
+        animator.play_clip(&clip);
+
+        animator.update_animation(0.0);
+        assert_eq!(event_names(&animator), vec!["start"]);
+
+        animator.update_animation(0.1);
+        assert!(event_names(&animator).is_empty());
+
+        // several events in one update fire in order
+        animator.update_animation(0.7);
+        assert_eq!(event_names(&animator), vec!["left_foot", "right_foot"]);
+
+        // wrapping past the end fires the events at the start of the next loop
+        animator.update_animation(0.5);
+        assert_eq!(event_names(&animator), vec!["start", "left_foot"]);
+        assert!((animator.current_animation.current_tick - 3.0).abs() < 1e-4);
+        assert!(animator.drain_events().is_empty());
+    }
+
+    #[test]
+    fn test_transition_events_are_weighted() {
+        let mut animator = test_animator();
+
+        let idle = Rc::new(AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever).with_event("idle_step", 5.0));
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+
+        animator.play_clip(&idle);
+        animator.update_animation(0.4);
+        animator.play_clip_with_transition(&run, Duration::from_secs(1));
+        animator.update_animation(0.2);
+
+        let events = animator.drain_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(&*events[0].name, "idle_step");
+        assert!((events[0].weight - 0.8).abs() < 1e-5);
+        assert!(Rc::ptr_eq(&events[0].animation_clip, &idle));
+    }
+
+    #[test]
+    fn test_counted_clip_completes() {
+        let mut animator = test_animator();
+
+        let clip = Rc::new(AnimationClip::new(0.0, 8.0, AnimationRepeat::Count(2)).with_event("start", 0.0));
+        animator.play_clip(&clip);
+
+        animator.update_animation(0.0);
+        animator.update_animation(1.2);
+        assert!(!animator.is_current_clip_finished());
+        assert_eq!(animator.current_animation.repeat_completions, 1);
+        assert!((animator.current_animation.current_tick - 4.0).abs() < 1e-4);
+        assert!(animator.drain_completed_clips().is_empty());
+
+        // the second loop ends and the last frame is held
+        animator.update_animation(0.6);
+        assert!(animator.is_current_clip_finished());
+        assert_eq!(animator.current_animation.current_tick, 8.0);
+        assert_eq!(event_names(&animator), vec!["start", "start"]);
+
+        animator.update_animation(1.0);
+        assert_eq!(animator.current_animation.current_tick, 8.0);
+        assert!(event_names(&animator).is_empty());
+
+        let completed = animator.drain_completed_clips();
+        assert_eq!(completed.len(), 1);
+        assert!(Rc::ptr_eq(&completed[0], &clip));
+    }
+
+    #[test]
+    fn test_fallback_clip() {
+        let mut animator = test_animator();
+
+        let idle = Rc::new(AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever));
+        let reload = Rc::new(AnimationClip::new(0.0, 5.0, AnimationRepeat::Once));
+        animator.set_fallback_clip(&idle, Duration::from_secs(1));
+
+        animator.play_clip(&reload);
+        animator.update_animation(0.0);
+        animator.update_animation(0.6);
+
+        assert!(Rc::ptr_eq(&animator.current_animation.animation_clip, &idle));
+        assert_eq!(animator.current_animation.current_tick, 0.0);
+        assert_eq!(animator.transitions.borrow().len(), 1);
+        assert_eq!(animator.drain_completed_clips().len(), 1);
+
+        // once faded out the node is posed by idle alone
+        animator.update_animation(0.5);
+        assert!(animator.transitions.borrow().is_empty());
+        assert!(!animator.is_current_clip_finished());
+    }
+
+    fn node_translation(animator: &Animator) -> Vec3 {
+        animator.get_node_transform("node").unwrap().transform.translation
+    }
+
+    #[test]
+    fn test_root_motion_wraps() {
+        let mut animator = test_animator();
+        animator.set_root_motion_node("node");
+
+        // idle moves the node from 0 to X over one second
+        let clip = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        animator.play_clip(&clip);
+
+        animator.update_animation(0.0);
+        animator.update_animation(0.5);
+        assert!(animator.take_root_motion().translation.abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::ZERO, 1e-5));
+
+        // the end of the loop and the start of the next one both count
+        animator.update_animation(0.7);
+        assert!((animator.current_animation.current_tick - 2.0).abs() < 1e-4);
+        assert!(animator.take_root_motion().translation.abs_diff_eq(Vec3::new(0.7, 0.0, 0.0), 1e-5));
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::ZERO, 1e-5));
+
+        assert_eq!(animator.take_root_motion().translation, Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_root_motion_transition() {
+        let mut animator = test_animator();
+        animator.set_root_motion_node("node");
+
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+
+        animator.play_clip(&idle);
+        animator.update_animation(0.0);
+        animator.update_animation(0.2);
+        animator.take_root_motion();
+
+        // run moves 0.25 Y and the fading idle 0.5 X, blended at the transition weight of 0.5
+        animator.play_clip_with_transition(&run, Duration::from_secs(1));
+        animator.update_animation(0.5);
+        assert!(animator
+            .take_root_motion()
+            .translation
+            .abs_diff_eq(Vec3::new(0.25, 0.125, 0.0), 1e-5));
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::ZERO, 1e-5));
+    }
+
+    #[test]
+    fn test_blend_space_root_motion() {
+        let mut animator = test_animator();
+        animator.set_root_motion_node("node");
+
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+        let mut blend_space = BlendSpace1D::new().add_sample(0.0, &idle).add_sample(1.0, &run);
+
+        // the blended cycle is 1.5 seconds, idle moves X and run Y over a cycle
+        let weighted = blend_space.update(&animator, 0.5, 0.0, 0.0);
+        animator.play_weight_animations(&weighted, 0.0);
+        assert_eq!(animator.take_root_motion().translation, Vec3::ZERO);
+
+        let weighted = blend_space.update(&animator, 0.5, 0.3, 0.3);
+        animator.play_weight_animations(&weighted, 0.3);
+        assert!(animator.take_root_motion().translation.abs_diff_eq(Vec3::new(0.1, 0.1, 0.0), 1e-4));
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::ZERO, 1e-5));
+
+        // from 0.2 of the cycle around to 0.1 of the next
+        let weighted = blend_space.update(&animator, 0.5, 1.35, 1.65);
+        animator.play_weight_animations(&weighted, 1.65);
+        assert!(animator
+            .take_root_motion()
+            .translation
+            .abs_diff_eq(Vec3::new(0.45, 0.45, 0.0), 1e-4));
+    }
+
+    #[test]
+    fn test_masked_layer() {
+        let mut animator = test_animator();
+
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+
+        animator.play_clip(&idle);
+        animator.set_layer("upper", &run, 1.0, BoneMask::subtree("node"));
+
+        // idle is at 0.5 X and run at 0.25 Y, which the layer overrides with
+        animator.update_animation(0.0);
+        animator.update_animation(0.5);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.0, 0.25, 0.0), 1e-5));
+
+        // the unanimated hand follows the node
+        let hand = animator.node_transforms.borrow().get("hand").unwrap().transform.translation;
+        assert!(hand.abs_diff_eq(Vec3::new(0.0, 0.25, 1.0), 1e-5));
+
+        animator.set_layer_weight("upper", 0.5);
+        animator.update_animation(0.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.25, 0.125, 0.0), 1e-5));
+
+        // a layer masked to other nodes leaves the node to the base pose
+        animator.set_layer("upper", &run, 1.0, BoneMask::nodes(&["hand"]));
+        animator.update_animation(0.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+
+        animator.remove_layer("upper");
+        assert!(animator.get_layer("upper").is_none());
+    }
+
+    #[test]
+    fn test_additive_layer() {
+        let mut animator = test_animator();
+
+        let index = animator.add_additive_animation("run_additive", 1, 0.0);
+        assert_eq!(animator.get_animation_index("run_additive"), Some(index));
+
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        let run_additive = animator.get_clip("run_additive", AnimationRepeat::Forever).unwrap();
+
+        animator.play_clip(&idle);
+        animator.set_additive_layer("recoil", &run_additive, 1.0, BoneMask::All);
+
+        // idle at 0.5 X with the run delta of 0.25 Y added
+        animator.update_animation(0.0);
+        animator.update_animation(0.5);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.5, 0.25, 0.0), 1e-5));
+
+        let hand = animator.node_transforms.borrow().get("hand").unwrap().transform.translation;
+        assert!(hand.abs_diff_eq(Vec3::new(0.5, 0.25, 1.0), 1e-5));
+
+        animator.set_layer_weight("recoil", 2.0);
+        animator.update_animation(0.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_weighted_animations_are_normalized() {
+        let mut animator = test_animator();
+
+        // at one second idle is at its end, X, and run half way, 0.5 Y
+        let weighted = [
+            WeightedAnimation::new(2.0, 0.0, 10.0, 9.99, 0.0),
+            WeightedAnimation::new(2.0, 0.0, 100.0, 0.0, 0.0).with_animation(1),
+        ];
+        animator.play_weight_animations(&weighted, 1.0);
+
+        let translation = node_translation(&animator);
+        assert!(translation.abs_diff_eq(Vec3::new(0.5, 0.25, 0.0), 1e-3));
+    }
+
+    #[test]
+    fn test_morph_weights() {
+        let mut animator = test_animator();
+        animator.pose_nodes[1].meshes = Rc::new(vec![0]);
+
+        // idle morphs from the first target to the second, run holds both at half
+        animator.animations[0].node_animations.borrow_mut()[0].morph_weights =
+            vec![KeyWeights::new(vec![1.0, 0.0], 0.0), KeyWeights::new(vec![0.0, 1.0], 10.0)];
+        animator.animations[1].node_animations.borrow_mut()[0].morph_weights = vec![KeyWeights::new(vec![0.5, 0.5], 0.0)];
+
+        let weighted = [
+            WeightedAnimation::new(1.0, 0.0, 10.0, 0.0, 0.0),
+            WeightedAnimation::new(1.0, 0.0, 100.0, 0.0, 0.0).with_animation(1),
+        ];
+        animator.play_weight_animations(&weighted, 0.5);
+
+        let weights = animator.mesh_morph_weights(0).unwrap();
+        assert!((weights[0] - 0.5).abs() < 1e-5 && (weights[1] - 0.5).abs() < 1e-5);
+
+        animator.play_weight_animations(&weighted[..1], 0.25);
+        let weights = animator.mesh_morph_weights(0).unwrap();
+        assert!((weights[0] - 0.75).abs() < 1e-5 && (weights[1] - 0.25).abs() < 1e-5);
+
+        // weights set by code win until cleared
+        animator.set_morph_weights("node", &[0.0, 0.0]);
+        assert_eq!(animator.mesh_morph_weights(0), Some(vec![0.0, 0.0]));
+        animator.clear_morph_weights("node");
+        assert!((animator.mesh_morph_weights(0).unwrap()[0] - 0.75).abs() < 1e-5);
+
+        assert_eq!(animator.mesh_morph_weights(1), None);
+    }
+
+    #[test]
+    fn test_morph_weights_fade_with_transition() {
+        let mut animator = test_animator();
+        animator.pose_nodes[1].meshes = Rc::new(vec![0]);
+
+        // idle holds the first target, run doesn't morph
+        animator.animations[0].node_animations.borrow_mut()[0].morph_weights = vec![KeyWeights::new(vec![1.0, 0.0], 0.0)];
+
+        animator.update_animation(0.1);
+        assert_eq!(animator.mesh_morph_weights(0), Some(vec![1.0, 0.0]));
+
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+        animator.play_clip_with_transition(&run, Duration::from_secs(1));
+
+        animator.update_animation(0.25);
+        assert!((animator.mesh_morph_weights(0).unwrap()[0] - 0.75).abs() < 1e-5);
+        animator.update_animation(0.5);
+        assert!((animator.mesh_morph_weights(0).unwrap()[0] - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_palettes_are_sized_from_the_model() {
+        let children = (0..60)
+            .map(|i| NodeData {
+                name: Rc::from(format!("mesh_node_{i}")),
+                transform: Transform::from_xyz(i as f32, 0.0, 0.0),
+                children: vec![],
+                meshes: Rc::new(vec![i]),
+            })
+            .collect();
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children,
+            meshes: Rc::new(vec![]),
+        };
+
+        // the last of 150 bones is the last mesh node
+        let mut bone_data_map = HashMap::new();
+        for i in 0..149 {
+            bone_data_map.insert(format!("bone_{i}"), BoneData::new(&format!("bone_{i}"), i, Mat4::IDENTITY));
+        }
+        bone_data_map.insert("mesh_node_59".to_string(), BoneData::new("mesh_node_59", 149, Mat4::IDENTITY));
+
+        let mut animator = Animator::from_node_data(root_node, vec![], RefCell::new(bone_data_map));
+        assert_eq!(animator.final_bone_matrices.borrow().len(), 150);
+        assert_eq!(animator.final_node_matrices.borrow().len(), 60);
+
+        animator.update_animation(0.1);
+        assert_eq!(animator.final_node_matrices.borrow()[59].w_axis.x, 59.0);
+        assert_eq!(animator.final_bone_matrices.borrow()[149].w_axis.x, 59.0);
+    }
+
+    #[test]
+    fn test_dual_quat_palette() {
+        let bone_transform = Transform::from_xyz(1.0, 2.0, 3.0).with_rotation(Quat::from_rotation_y(1.2));
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![NodeData {
+                name: Rc::from("bone"),
+                transform: bone_transform,
+                children: vec![],
+                meshes: Rc::new(vec![]),
+            }],
+            meshes: Rc::new(vec![]),
+        };
+
+        let mut bone_data_map = HashMap::new();
+        bone_data_map.insert("bone".to_string(), BoneData::new("bone", 0, Mat4::IDENTITY));
+
+        let mut animator = Animator::from_node_data(root_node, vec![], RefCell::new(bone_data_map));
+
+        // only filled for dual quaternion skinning
+        animator.update_animation(0.1);
+        assert_eq!(animator.final_bone_dual_quats.borrow()[0], DualQuat::IDENTITY);
+
+        animator.skinning_method = SkinningMethod::DualQuaternion;
+        animator.update_animation(0.1);
+
+        let point = Vec3::new(0.5, -1.0, 2.0);
+        let dual_quat = animator.final_bone_dual_quats.borrow()[0];
+        let matrix = animator.final_bone_matrices.borrow()[0];
+        assert!(dual_quat.transform_point(point).abs_diff_eq(matrix.transform_point3(point), 1e-5));
+    }
+
+    #[test]
+    fn test_pose_cache_is_shared() {
+        // one sample per tick of the idle animation
+        let pose_cache = Rc::new(PoseCache::new(10.0));
+
+        let mut first = test_animator();
+        first.set_pose_cache(&pose_cache);
+        let mut second = first.clone();
+        let mut uncached = test_animator();
+
+        // ticks 5.2 and 4.8 share the pose at tick 5
+        first.update_animation(0.52);
+        second.update_animation(0.48);
+        uncached.update_animation(0.5);
+
+        assert_eq!(pose_cache.len(), 1);
+        assert_eq!((pose_cache.hits(), pose_cache.misses()), (1, 1));
+        assert!(node_translation(&second).abs_diff_eq(node_translation(&uncached), 1e-5));
+        assert_eq!(first.final_node_matrices, second.final_node_matrices);
+
+        // the hit holds the cached pose rather than a copy of it
+        assert!(first.cached_pose.borrow().is_none());
+        assert!(second.cached_pose.borrow().is_some());
+
+        // the clips keep their own ticks
+        assert!((first.current_animation.current_tick - 5.2).abs() < 1e-4);
+
+        // transitions are sampled by each animator
+        let run = first.get_clip("run", AnimationRepeat::Forever).unwrap();
+        first.play_clip_with_transition(&run, Duration::from_secs(1));
+        first.update_animation(0.1);
+        assert_eq!(pose_cache.len(), 1);
+    }
+
+    #[test]
+    fn test_pose_cache_keeps_skeletons_apart() {
+        let pose_cache = Rc::new(PoseCache::new(10.0));
+
+        let mut first = test_animator();
+        first.set_pose_cache(&pose_cache);
+
+        // the same animation names on a skeleton with a longer hand
+        let mut root_node = first.root_node.clone();
+        root_node.children[0].children[0].transform = Transform::from_xyz(0.0, 0.0, 2.0);
+        let animations = vec![
+            moving_node_animation("idle", 10.0, 10.0, Vec3::X),
+            moving_node_animation("run", 100.0, 50.0, Vec3::Y),
+        ];
+        let mut second = Animator::from_node_data(root_node, animations, RefCell::new(HashMap::new()));
+        second.animations = first.animations.clone();
+        second.set_pose_cache(&pose_cache);
+
+        assert_eq!(first.skeleton_id, test_animator().skeleton_id);
+        assert_ne!(first.skeleton_id, second.skeleton_id);
+
+        first.update_animation(0.5);
+        second.update_animation(0.5);
+
+        assert_eq!(pose_cache.len(), 2);
+        assert_eq!(pose_cache.hits(), 0);
+        assert!((first.get_node_transform("hand").unwrap().transform.translation.z - 1.0).abs() < 1e-5);
+        assert!((second.get_node_transform("hand").unwrap().transform.translation.z - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_pose_cache_keeps_animations_apart() {
+        let pose_cache = Rc::new(PoseCache::new(10.0));
+
+        let mut first = test_animator();
+        first.set_pose_cache(&pose_cache);
+
+        // a second "idle", such as a retargeted copy, that moves the node along Z
+        let copy_index = first.add_animation(moving_node_animation("idle", 10.0, 10.0, Vec3::Z));
+        let mut second = first.clone();
+        let copy = Rc::new(AnimationClip::new(0.0, 10.0, AnimationRepeat::Forever).with_animation(copy_index));
+        second.play_clip(&copy);
+
+        first.update_animation(0.5);
+        second.update_animation(0.5);
+
+        assert_eq!(pose_cache.len(), 2);
+        assert_eq!(pose_cache.hits(), 0);
+        assert!(node_translation(&first).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+        assert!(node_translation(&second).abs_diff_eq(Vec3::new(0.0, 0.0, 0.5), 1e-5));
+    }
+
+    #[test]
+    fn test_blend_from_captured_pose() {
+        let mut animator = test_animator();
+
+        // idle moves the node to x = 0.5 by tick 5
+        animator.update_animation(0.5);
+        let pose = animator.capture_pose();
+        assert!(pose
+            .get_local_transform("node")
+            .unwrap()
+            .translation
+            .abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+
+        // run starts at the origin, a quarter of the way through the blend it's still mostly the captured pose
+        let run = animator.get_clip("run", AnimationRepeat::Forever).unwrap();
+        animator.play_clip(&run);
+        animator.blend_from_pose(&pose, Duration::from_secs(1));
+        animator.update_animation(0.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+
+        animator.update_animation(0.25);
+        let run_translation = Vec3::new(0.0, 0.125, 0.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0) * 0.75 + run_translation * 0.25, 1e-5));
+
+        animator.update_animation(1.0);
+        assert!(animator.pose_transition.is_none());
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.0, 0.625, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_hold_procedural_pose() {
+        let mut animator = test_animator();
+        animator.update_animation(0.5);
+
+        let captured = animator.capture_pose();
+        let mut procedural = captured.clone();
+        procedural.set_local_transform("node", Transform::from_xyz(0.0, 0.0, -1.0));
+
+        animator.hold_pose(&procedural);
+        animator.blend_from_pose(&captured, Duration::from_secs(1));
+        animator.update_animation(0.5);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.25, 0.0, -0.5), 1e-5));
+
+        // the held pose stays once the blend is done, and the clip is paused
+        animator.update_animation(1.0);
+        assert!(node_translation(&animator).abs_diff_eq(Vec3::new(0.0, 0.0, -1.0), 1e-5));
+        assert!((animator.current_animation.current_tick - 5.0).abs() < 1e-4);
+
+        // playing a clip fades out the held pose
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        animator.play_clip_with_transition(&idle, Duration::from_secs(1));
+        assert!(animator.held_pose.is_none());
+        assert!(animator.pose_transition.is_some());
+    }
+
+    #[test]
+    fn test_play_clip_during_pose_blend() {
+        let mut animator = test_animator();
+        animator.update_animation(0.5);
+
+        let captured = animator.capture_pose();
+        let mut procedural = captured.clone();
+        procedural.set_local_transform("node", Transform::from_xyz(0.0, 0.0, -1.0));
+
+        animator.hold_pose(&procedural);
+        animator.blend_from_pose(&captured, Duration::from_secs(1));
+        animator.update_animation(0.5);
+        let blended = node_translation(&animator);
+        assert!(blended.abs_diff_eq(Vec3::new(0.25, 0.0, -0.5), 1e-5));
+
+        // the clip fades in from where the blend was rather than from the held pose
+        let idle = animator.get_clip("idle", AnimationRepeat::Forever).unwrap();
+        animator.play_clip_with_transition(&idle, Duration::from_secs(1));
+        animator.update_animation(0.0);
+        assert!(node_translation(&animator).abs_diff_eq(blended, 1e-5));
+    }
+
+    #[test]
+    fn test_apply_bone_transforms() {
+        let mut bone_data_map = HashMap::new();
+        bone_data_map.insert("node".to_string(), BoneData::new("node", 0, Mat4::IDENTITY));
+        bone_data_map.insert("hand".to_string(), BoneData::new("hand", 1, Mat4::IDENTITY));
+
+        let mut animator = test_animator();
+        *animator.bone_data_map.borrow_mut() = bone_data_map;
+        *animator.final_bone_matrices.borrow_mut() = vec![Mat4::IDENTITY; 2].into_boxed_slice();
+        animator.update_animation(0.5);
+
+        // a physics step moved the node, the hand wasn't simulated and stays where it was animated
+        let simulated = Transform::from_xyz(2.0, 0.0, 0.0).with_rotation(Quat::from_rotation_y(FRAC_PI_2));
+        animator.apply_bone_transforms(&[("node", simulated)]);
+
+        assert!(animator.final_bone_matrices.borrow()[0].abs_diff_eq(simulated.compute_matrix(), 1e-5));
+        let hand = Mat4::from_translation(Vec3::new(0.5, 0.0, 1.0));
+        assert!(animator.final_bone_matrices.borrow()[1].abs_diff_eq(hand, 1e-5));
+
+        // the captured local pose reproduces the applied transforms
+        let pose = animator.capture_pose();
+        let hand_local = pose.get_local_transform("hand").unwrap();
+        assert!(simulated.mul_transform(hand_local).compute_matrix().abs_diff_eq(hand, 1e-5));
+
+        animator.hold_pose(&pose);
+        animator.update_animation(0.1);
+        assert!(animator.final_bone_matrices.borrow()[0].abs_diff_eq(simulated.compute_matrix(), 1e-5));
+        assert!(animator.final_bone_matrices.borrow()[1].abs_diff_eq(hand, 1e-5));
+    }
 }
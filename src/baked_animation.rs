@@ -0,0 +1,453 @@
+use crate::animator::Animator;
+use crate::error::Error;
+use crate::error::Error::TextureError;
+use crate::gpu_context::GpuContext;
+use glam::{Mat4, Vec4};
+use std::mem;
+use std::rc::Rc;
+use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout, Buffer};
+
+pub const BAKED_ANIMATION_BIND_GROUP_LAYOUT: &str = "baked_animation_bind_group_layout";
+
+/// The most animations one bake can hold, the size of the clip table uniform.
+pub const MAX_BAKED_CLIPS: usize = 64;
+
+const OFFSET_OF_CLIP_INDEX: usize = mem::offset_of!(BakedInstance, clip_index);
+const OFFSET_OF_TIME: usize = mem::offset_of!(BakedInstance, time);
+
+/// Where an animation's frames are in the baked texture, one row per frame. The layout matches the WGSL `BakedClip`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BakedClip {
+    pub first_row: u32,
+    pub frame_count: u32,
+    /// In seconds.
+    pub duration: f32,
+    pub frames_per_second: f32,
+}
+
+/// The animations of a model sampled at a fixed rate. Each row holds a frame's palettes, the bones then the nodes,
+/// a matrix as four texels of its columns.
+#[derive(Debug, Clone)]
+pub struct BakedAnimationData {
+    pub frames_per_second: f32,
+    pub bone_count: u32,
+    pub node_count: u32,
+    pub clip_names: Vec<Rc<str>>,
+    pub clips: Vec<BakedClip>,
+    pub texels: Vec<Vec4>,
+}
+
+impl BakedAnimationData {
+    /// Samples every animation of the animator from its start to its end, the last frame at the end itself.
+    pub fn bake(animator: &Animator, frames_per_second: f32) -> BakedAnimationData {
+        let frames_per_second = frames_per_second.max(f32::EPSILON);
+        let bone_count = animator.final_bone_matrices.borrow().len() as u32;
+        let node_count = animator.final_node_matrices.borrow().len() as u32;
+
+        let mut clip_names = vec![];
+        let mut clips = vec![];
+        let mut texels = vec![];
+        let mut row = 0;
+
+        for animation_index in 0..animator.animation_count() {
+            let animation = animator.get_animation(animation_index).unwrap();
+            let duration = if animation.ticks_per_second > 0.0 {
+                animation.duration / animation.ticks_per_second
+            } else {
+                0.0
+            };
+            let frame_count = (duration * frames_per_second).ceil() as u32 + 1;
+
+            for frame in 0..frame_count {
+                let time = (frame as f32 / frames_per_second).min(duration);
+                let (bones, nodes) = animator.sample_palettes(animation_index, time * animation.ticks_per_second);
+
+                for matrix in bones.iter().chain(nodes.iter()) {
+                    texels.extend_from_slice(&[matrix.x_axis, matrix.y_axis, matrix.z_axis, matrix.w_axis]);
+                }
+            }
+
+            clip_names.push(animation.name.clone());
+            clips.push(BakedClip {
+                first_row: row,
+                frame_count,
+                duration,
+                frames_per_second,
+            });
+            row += frame_count;
+        }
+
+        BakedAnimationData {
+            frames_per_second,
+            bone_count,
+            node_count,
+            clip_names,
+            clips,
+            texels,
+        }
+    }
+
+    /// The texture's width in texels.
+    pub fn width(&self) -> u32 {
+        (self.bone_count + self.node_count) * 4
+    }
+
+    /// The texture's height, the number of frames of all the animations.
+    pub fn height(&self) -> u32 {
+        self.clips.iter().map(|clip| clip.frame_count).sum()
+    }
+
+    pub fn clip_index(&self, name: &str) -> Option<u32> {
+        self.clip_names
+            .iter()
+            .position(|clip_name| clip_name.as_ref() == name)
+            .map(|index| index as u32)
+    }
+
+    /// The matrix of the palette entry, bones first then nodes, at the frame's row.
+    pub fn matrix(&self, entry: u32, row: u32) -> Mat4 {
+        let index = (row * self.width() + entry * 4) as usize;
+        Mat4::from_cols(
+            self.texels[index],
+            self.texels[index + 1],
+            self.texels[index + 2],
+            self.texels[index + 3],
+        )
+    }
+}
+
+/// The uniform holding the palette sizes and the clip table, matches the WGSL `BakedAnimationInfo`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakedAnimationInfo {
+    bone_count: u32,
+    node_count: u32,
+    clip_count: u32,
+    padding: u32,
+    clips: [BakedClip; MAX_BAKED_CLIPS],
+}
+
+/// The baked animations on the gpu, a float texture of the palettes and the clip table. Models drawn with it are
+/// skinned in the vertex shader from their instance's clip and time, with no animator updates or palette uploads.
+/// Morph targets aren't baked.
+#[derive(Debug)]
+pub struct BakedAnimations {
+    pub data: BakedAnimationData,
+    pub texture: wgpu::Texture,
+    pub info_buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+impl BakedAnimations {
+    pub fn new(context: &mut GpuContext, data: BakedAnimationData) -> Result<BakedAnimations, Error> {
+        if data.width() == 0 || data.height() == 0 {
+            return Err(TextureError(String::from("no bones, nodes or animations to bake")));
+        }
+
+        let max_dimension = context.device.limits().max_texture_dimension_2d;
+        if data.width() > max_dimension || data.height() > max_dimension {
+            return Err(TextureError(format!(
+                "baked animations of {}x{} texels are larger than the maximum texture size of {}",
+                data.width(),
+                data.height(),
+                max_dimension
+            )));
+        }
+
+        if data.clips.len() > MAX_BAKED_CLIPS {
+            return Err(TextureError(format!(
+                "{} baked animations, more than the maximum of {}",
+                data.clips.len(),
+                MAX_BAKED_CLIPS
+            )));
+        }
+
+        let size = wgpu::Extent3d {
+            width: data.width(),
+            height: data.height(),
+            depth_or_array_layers: 1,
+        };
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("baked animation texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        context.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&data.texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(data.width() * mem::size_of::<Vec4>() as u32),
+                rows_per_image: Some(data.height()),
+            },
+            size,
+        );
+
+        let mut info = BakedAnimationInfo {
+            bone_count: data.bone_count,
+            node_count: data.node_count,
+            clip_count: data.clips.len() as u32,
+            padding: 0,
+            clips: [BakedClip::default(); MAX_BAKED_CLIPS],
+        };
+        info.clips[..data.clips.len()].copy_from_slice(&data.clips);
+
+        let info_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("baked animation info"),
+            contents: bytemuck::bytes_of(&info),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        if !context.bind_layout_cache.contains_key(BAKED_ANIMATION_BIND_GROUP_LAYOUT) {
+            let layout = create_baked_animation_bind_group_layout(context);
+            context
+                .bind_layout_cache
+                .insert(String::from(BAKED_ANIMATION_BIND_GROUP_LAYOUT), layout.into());
+        }
+
+        let bind_group_layout = context.bind_layout_cache.get(BAKED_ANIMATION_BIND_GROUP_LAYOUT).unwrap();
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: info_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("baked animation bind group"),
+        });
+
+        Ok(BakedAnimations {
+            data,
+            texture,
+            info_buffer,
+            bind_group,
+        })
+    }
+
+    pub fn clip_index(&self, name: &str) -> Option<u32> {
+        self.data.clip_index(name)
+    }
+}
+
+/// One model drawn with baked animations, the per instance vertex data at shader locations 7 to 12,
+/// after those of `ModelVertex`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BakedInstance {
+    pub model_transform: Mat4,
+    pub clip_index: u32,
+    /// Seconds into the clip, wrapped around its duration.
+    pub time: f32,
+    pub padding: [u32; 2],
+}
+
+impl BakedInstance {
+    pub fn new(model_transform: Mat4, clip_index: u32, time: f32) -> Self {
+        BakedInstance {
+            model_transform,
+            clip_index,
+            time,
+            padding: [0; 2],
+        }
+    }
+
+    pub fn vertex_description() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<BakedInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                // model transform columns
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<Vec4>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<Vec4>() * 2) as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<Vec4>() * 3) as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // clip index
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_CLIP_INDEX as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                // time
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_TIME as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// The WGSL declarations of the baked animation bind group at `group` and `baked_skin_matrix`, which blends the
+/// vertex's bones at the instance's clip and time, or gives the mesh's node transform for vertices without bones.
+pub fn baked_animation_wgsl(group: u32) -> String {
+    format!(
+        "const MAX_BAKED_CLIPS: u32 = {MAX_BAKED_CLIPS}u;
+
+struct BakedClip {{
+    first_row: u32,
+    frame_count: u32,
+    duration: f32,
+    frames_per_second: f32,
+}}
+
+struct BakedAnimationInfo {{
+    bone_count: u32,
+    node_count: u32,
+    clip_count: u32,
+    padding: u32,
+    clips: array<BakedClip, MAX_BAKED_CLIPS>,
+}}
+
+@group({group}) @binding(0) var baked_palettes: texture_2d<f32>;
+@group({group}) @binding(1) var<uniform> baked_animation: BakedAnimationInfo;
+
+{}",
+        include_str!("shaders/baked_animation.wgsl")
+    )
+}
+
+fn create_baked_animation_bind_group_layout(context: &GpuContext) -> BindGroupLayout {
+    context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            // 0: baked palettes
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // 1: palette sizes and clip table
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+        label: Some("baked animation bind group layout"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animator::Animator;
+    use crate::baked_animation::BakedAnimationData;
+    use crate::hash_map::HashMap;
+    use crate::model_animation::{BoneData, ModelAnimation, NodeData};
+    use crate::node_animation::{KeyPosition, KeyRotation, KeyScale, NodeAnimation};
+    use crate::transform::Transform;
+    use glam::{Mat4, Quat, Vec3};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_bake_matches_sampled_palettes() {
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![NodeData {
+                name: Rc::from("bone"),
+                transform: Transform::IDENTITY,
+                children: vec![],
+                meshes: Rc::new(vec![0]),
+            }],
+            meshes: Rc::new(vec![]),
+        };
+
+        // 1.3 seconds, so the last frame is closer to the one before it than the frame rate
+        let node_animation = NodeAnimation::from_keys(
+            Rc::from("bone"),
+            vec![
+                KeyPosition::new(Vec3::ZERO, 0.0),
+                KeyPosition::new(Vec3::new(0.0, 2.6, 0.0), 1300.0),
+            ],
+            vec![
+                KeyRotation::new(Quat::IDENTITY, 0.0),
+                KeyRotation::new(Quat::from_rotation_z(1.0), 1300.0),
+            ],
+            vec![KeyScale::new(Vec3::ONE, 0.0)],
+        );
+        let animation = ModelAnimation {
+            name: Rc::from("wave"),
+            duration: 1300.0,
+            ticks_per_second: 1000.0,
+            node_animations: RefCell::new(vec![node_animation]),
+        };
+
+        let mut bone_data_map = HashMap::new();
+        bone_data_map.insert("bone".to_string(), BoneData::new("bone", 0, Mat4::from_translation(Vec3::X)));
+
+        let animator = Animator::from_node_data(root_node, vec![animation], RefCell::new(bone_data_map));
+        let baked = BakedAnimationData::bake(&animator, 4.0);
+
+        assert_eq!(baked.clip_index("wave"), Some(0));
+        assert_eq!(baked.clips[0].frame_count, 7);
+        assert_eq!(baked.clips[0].duration, 1.3);
+        assert_eq!(baked.width(), 8);
+        assert_eq!(baked.height(), 7);
+        assert_eq!(baked.texels.len(), 8 * 7);
+
+        for frame in 0..7 {
+            let time = (frame as f32 / 4.0).min(1.3);
+            let (bones, nodes) = animator.sample_palettes(0, time * 1000.0);
+
+            assert!(baked.matrix(0, frame).abs_diff_eq(bones[0], 1e-6));
+            assert!(baked.matrix(1, frame).abs_diff_eq(nodes[0], 1e-6));
+        }
+
+        // the last frame is the end of the animation
+        let end = Mat4::from_rotation_translation(Quat::from_rotation_z(1.0), Vec3::new(0.0, 2.6, 0.0));
+        assert!(baked.matrix(1, 6).abs_diff_eq(end, 1e-5));
+        assert!(baked.matrix(0, 6).abs_diff_eq(end * Mat4::from_translation(Vec3::X), 1e-5));
+
+        // sampling doesn't touch the animator's palettes
+        assert_eq!(animator.final_bone_matrices.borrow()[0], Mat4::IDENTITY);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::animator::{AnimationClip, Animator, WeightedAnimation};
+use glam::Vec2;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct BlendSample<P> {
+    pub position: P,
+    pub clip: Rc<AnimationClip>,
+}
+
+/// Blends clips placed along a single parameter, such as speed.
+#[derive(Debug, Clone)]
+pub struct BlendSpace1D {
+    pub samples: Vec<BlendSample<f32>>,
+    phase: f32,
+}
+
+impl Default for BlendSpace1D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlendSpace1D {
+    pub fn new() -> Self {
+        BlendSpace1D {
+            samples: vec![],
+            phase: 0.0,
+        }
+    }
+
+    pub fn add_sample(mut self, position: f32, clip: &Rc<AnimationClip>) -> Self {
+        self.samples.push(BlendSample {
+            position,
+            clip: clip.clone(),
+        });
+        self.samples.sort_by(|a, b| a.position.total_cmp(&b.position));
+        self
+    }
+
+    /// The weight of each sample for `value`, summing to 1.0. Values outside the samples
+    /// are clamped to the first or last sample.
+    pub fn sample_weights(&self, value: f32) -> Vec<f32> {
+        let mut weights = vec![0.0; self.samples.len()];
+
+        if self.samples.is_empty() {
+            return weights;
+        }
+
+        let last = self.samples.len() - 1;
+
+        if value <= self.samples[0].position {
+            weights[0] = 1.0;
+        } else if value >= self.samples[last].position {
+            weights[last] = 1.0;
+        } else {
+            let index = self.samples.iter().rposition(|s| s.position <= value).unwrap();
+            let start = self.samples[index].position;
+            let end = self.samples[index + 1].position;
+            let t = (value - start) / (end - start);
+            weights[index] = 1.0 - t;
+            weights[index + 1] = t;
+        }
+
+        weights
+    }
+
+    /// Advances the shared phase and returns the weighted animations for `Animator::play_weight_animations`
+    /// called with the same `frame_time`.
+    pub fn update(&mut self, animator: &Animator, value: f32, delta_time: f32, frame_time: f32) -> Vec<WeightedAnimation> {
+        let weights = self.sample_weights(value);
+        let clips = self.samples.iter().map(|s| &s.clip);
+        synced_weighted_animations(&mut self.phase, clips.zip(weights), animator, delta_time, frame_time)
+    }
+}
+
+/// Blends clips placed on a plane, such as run directions, using gradient band interpolation.
+#[derive(Debug, Clone)]
+pub struct BlendSpace2D {
+    pub samples: Vec<BlendSample<Vec2>>,
+    phase: f32,
+}
+
+impl Default for BlendSpace2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlendSpace2D {
+    pub fn new() -> Self {
+        BlendSpace2D {
+            samples: vec![],
+            phase: 0.0,
+        }
+    }
+
+    pub fn add_sample(mut self, position: Vec2, clip: &Rc<AnimationClip>) -> Self {
+        self.samples.push(BlendSample {
+            position,
+            clip: clip.clone(),
+        });
+        self
+    }
+
+    /// The weight of each sample for `point`, summing to 1.0. A point on a sample gives that
+    /// sample all the weight.
+    pub fn sample_weights(&self, point: Vec2) -> Vec<f32> {
+        let mut weights: Vec<f32> = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample_i)| {
+                let to_point = point - sample_i.position;
+
+                self.samples
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, sample_j)| {
+                        let to_sample = sample_j.position - sample_i.position;
+                        let length_squared = to_sample.length_squared();
+                        if length_squared == 0.0 {
+                            return 1.0;
+                        }
+                        1.0 - to_point.dot(to_sample) / length_squared
+                    })
+                    .fold(1.0f32, f32::min)
+                    .max(0.0)
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+
+        if total > 0.0 {
+            weights.iter_mut().for_each(|w| *w /= total);
+        } else if !weights.is_empty() {
+            weights[0] = 1.0;
+        }
+
+        weights
+    }
+
+    /// Advances the shared phase and returns the weighted animations for `Animator::play_weight_animations`
+    /// called with the same `frame_time`.
+    pub fn update(&mut self, animator: &Animator, point: Vec2, delta_time: f32, frame_time: f32) -> Vec<WeightedAnimation> {
+        let weights = self.sample_weights(point);
+        let clips = self.samples.iter().map(|s| &s.clip);
+        synced_weighted_animations(&mut self.phase, clips.zip(weights), animator, delta_time, frame_time)
+    }
+}
+
+/// Plays all the clips at the same normalized phase so that their cycles, such as foot steps, line up.
+/// The phase advances at the rate of the weighted average clip length.
+fn synced_weighted_animations<'a>(
+    phase: &mut f32,
+    clip_weights: impl Iterator<Item = (&'a Rc<AnimationClip>, f32)>,
+    animator: &Animator,
+    delta_time: f32,
+    frame_time: f32,
+) -> Vec<WeightedAnimation> {
+    let clip_weights: Vec<(&Rc<AnimationClip>, f32)> = clip_weights.filter(|(_, weight)| *weight > 0.0).collect();
+
+    let cycle_seconds: f32 = clip_weights
+        .iter()
+        .map(|(clip, weight)| {
+            let ticks_per_second = animator.animation(clip.animation_index).ticks_per_second;
+            weight * (clip.end_tick - clip.start_tick) / ticks_per_second
+        })
+        .sum();
+
+    if cycle_seconds > 0.0 {
+        *phase = (*phase + delta_time / cycle_seconds).fract();
+    }
+
+    clip_weights
+        .iter()
+        .map(|(clip, weight)| {
+            let ticks_per_second = animator.animation(clip.animation_index).ticks_per_second;
+            let tick_range = clip.end_tick - clip.start_tick;
+
+            // play_weight_animations derives the tick from frame_time, the offset moves it to the phase
+            let offset = if tick_range > 0.0 {
+                (*phase * tick_range - frame_time * ticks_per_second).rem_euclid(tick_range)
+            } else {
+                0.0
+            };
+
+            WeightedAnimation::new(*weight, clip.start_tick, clip.end_tick, offset, 0.0).with_animation(clip.animation_index)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animator::{AnimationClip, AnimationRepeat, Animator};
+    use crate::blend_space::{BlendSpace1D, BlendSpace2D};
+    use crate::hash_map::HashMap;
+    use crate::model_animation::{ModelAnimation, NodeData};
+    use crate::transform::Transform;
+    use glam::{vec2, Vec2};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn clip(start_tick: f32, end_tick: f32) -> Rc<AnimationClip> {
+        Rc::new(AnimationClip::new(start_tick, end_tick, AnimationRepeat::Forever))
+    }
+
+    fn test_animator() -> Animator {
+        let root_node = NodeData {
+            name: Rc::from("root"),
+            transform: Transform::IDENTITY,
+            children: vec![],
+            meshes: Rc::new(vec![]),
+        };
+
+        let animation = ModelAnimation {
+            name: Rc::from("all"),
+            duration: 100.0,
+            ticks_per_second: 10.0,
+            node_animations: RefCell::new(vec![]),
+        };
+
+        Animator::from_node_data(root_node, vec![animation], RefCell::new(HashMap::new()))
+    }
+
+    #[test]
+    fn test_1d_weights() {
+        let blend_space = BlendSpace1D::new()
+            .add_sample(4.0, &clip(20.0, 30.0))
+            .add_sample(0.0, &clip(0.0, 10.0))
+            .add_sample(1.0, &clip(10.0, 20.0));
+
+        assert_eq!(blend_space.samples[0].position, 0.0);
+        assert_eq!(blend_space.sample_weights(-1.0), vec![1.0, 0.0, 0.0]);
+        assert_eq!(blend_space.sample_weights(0.25), vec![0.75, 0.25, 0.0]);
+        assert_eq!(blend_space.sample_weights(2.5), vec![0.0, 0.5, 0.5]);
+        assert_eq!(blend_space.sample_weights(10.0), vec![0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_2d_weights() {
+        let blend_space = BlendSpace2D::new()
+            .add_sample(Vec2::ZERO, &clip(0.0, 10.0))
+            .add_sample(vec2(0.0, 1.0), &clip(10.0, 20.0))
+            .add_sample(vec2(1.0, 0.0), &clip(20.0, 30.0))
+            .add_sample(vec2(0.0, -1.0), &clip(30.0, 40.0))
+            .add_sample(vec2(-1.0, 0.0), &clip(40.0, 50.0));
+
+        let weights = blend_space.sample_weights(vec2(0.0, 1.0));
+        assert_eq!(weights, vec![0.0, 1.0, 0.0, 0.0, 0.0]);
+
+        let weights = blend_space.sample_weights(vec2(0.0, 0.5));
+        assert!((weights[0] - 0.5).abs() < 1e-5);
+        assert!((weights[1] - 0.5).abs() < 1e-5);
+
+        let weights = blend_space.sample_weights(vec2(0.3, 0.6));
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(weights[1] > weights[2]);
+        assert_eq!(weights[3], 0.0);
+        assert_eq!(weights[4], 0.0);
+    }
+
+    #[test]
+    fn test_synced_phase() {
+        let animator = test_animator();
+
+        // a slow and a fast cycle, 10 and 30 ticks long
+        let mut blend_space = BlendSpace1D::new()
+            .add_sample(0.0, &clip(0.0, 10.0))
+            .add_sample(1.0, &clip(10.0, 40.0));
+
+        let frame_time = 12.3;
+        let weighted = blend_space.update(&animator, 0.5, 0.5, frame_time);
+        assert_eq!(weighted.len(), 2);
+
+        // the cycle is 2 seconds at half weight each, so half a second is a quarter of the way
+        for weighted_animation in weighted.iter() {
+            let tick_range = weighted_animation.end_tick - weighted_animation.start_tick;
+            let tick = (frame_time * 10.0 + weighted_animation.offset) % tick_range;
+            assert!((tick / tick_range - 0.25).abs() < 1e-3);
+        }
+    }
+}
@@ -113,7 +113,7 @@ impl FlyCameraController {
     }
 
     pub fn resize(&mut self, context: &GpuContext) {
-        let size = context.window.inner_size();
+        let size = context.size;
         self.aspect_ratio = size.width as f32 / size.height as f32;
     }
 }
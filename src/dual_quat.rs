@@ -0,0 +1,116 @@
+use crate::transform::Transform;
+use glam::{Quat, Vec3, Vec4};
+
+/// A bone transform for dual quaternion skinning, a unit dual quaternion for the rotation and translation
+/// and the scale applied before it. Blending dual quaternions keeps the volume that blending matrices
+/// loses around twisting joints. The layout matches the `DualQuat` struct of the WGSL palettes.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DualQuat {
+    pub real: Quat,
+    pub dual: Quat,
+    /// xyz is the scale, w is unused.
+    pub scale: Vec4,
+}
+
+impl DualQuat {
+    pub const IDENTITY: Self = DualQuat {
+        real: Quat::IDENTITY,
+        dual: Quat::from_xyzw(0.0, 0.0, 0.0, 0.0),
+        scale: Vec4::new(1.0, 1.0, 1.0, 0.0),
+    };
+
+    pub fn from_transform(transform: &Transform) -> Self {
+        let real = transform.rotation.normalize();
+        let translation = Quat::from_xyzw(transform.translation.x, transform.translation.y, transform.translation.z, 0.0);
+
+        DualQuat {
+            real,
+            dual: translation * real * 0.5,
+            scale: transform.scale.extend(0.0),
+        }
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        let translation = self.dual * self.real.conjugate() * 2.0;
+        Vec3::new(translation.x, translation.y, translation.z)
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.real * (point * self.scale.truncate()) + self.translation()
+    }
+
+    /// The weighted sum of the dual quaternions, flipped into the hemisphere of the first and normalized,
+    /// the same as the WGSL `blend_dual_quats`. None when the weights are all zero.
+    pub fn blend(dual_quats: &[(DualQuat, f32)]) -> Option<DualQuat> {
+        let (first, _) = dual_quats.first()?;
+
+        let mut real = Vec4::ZERO;
+        let mut dual = Vec4::ZERO;
+        let mut scale = Vec4::ZERO;
+        let mut total_weight = 0.0;
+
+        for (dual_quat, weight) in dual_quats {
+            // q and -q are the same rotation, blending them needs them on the same side
+            let sign = if first.real.dot(dual_quat.real) < 0.0 { -1.0 } else { 1.0 };
+
+            real += Vec4::from(dual_quat.real) * sign * *weight;
+            dual += Vec4::from(dual_quat.dual) * sign * *weight;
+            scale += dual_quat.scale * *weight;
+            total_weight += weight;
+        }
+
+        let length = real.length();
+        if total_weight <= 0.0 || length <= 0.0 {
+            return None;
+        }
+
+        Some(DualQuat {
+            real: Quat::from_vec4(real / length),
+            dual: Quat::from_vec4(dual / length),
+            scale: scale / total_weight,
+        })
+    }
+}
+
+impl Default for DualQuat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dual_quat::DualQuat;
+    use crate::transform::Transform;
+    use glam::{Quat, Vec3};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_matches_transform() {
+        let transform = Transform::from_xyz(1.0, -2.0, 3.0)
+            .with_rotation(Quat::from_rotation_y(0.7) * Quat::from_rotation_x(-0.3))
+            .with_scale(Vec3::new(2.0, 0.5, 1.0));
+        let dual_quat = DualQuat::from_transform(&transform);
+
+        let point = Vec3::new(0.5, 4.0, -1.0);
+        assert!(dual_quat.transform_point(point).abs_diff_eq(transform.transform_point(point), 1e-5));
+        assert!(dual_quat.translation().abs_diff_eq(transform.translation, 1e-5));
+    }
+
+    #[test]
+    fn test_blend_keeps_volume() {
+        // a wrist twisted half a turn, blending matrices collapses the point onto the axis
+        let rest = DualQuat::from_transform(&Transform::IDENTITY);
+        let twisted = DualQuat::from_transform(&Transform::from_rotation(Quat::from_rotation_x(PI * 0.9)));
+
+        let point = Vec3::new(0.0, 1.0, 0.0);
+        let blended = DualQuat::blend(&[(rest, 0.5), (twisted, 0.5)]).unwrap();
+        assert!((blended.transform_point(point).length() - 1.0).abs() < 1e-5);
+
+        let linear = (point + Quat::from_rotation_x(PI * 0.9) * point) * 0.5;
+        assert!(linear.length() < 0.2);
+
+        assert!(DualQuat::blend(&[(rest, 0.0)]).is_none());
+    }
+}
@@ -4,10 +4,13 @@ pub enum Error {
     FileError(std::io::Error),
     ShaderError(String),
     ImageError(String),
+    #[cfg(feature = "russimp")]
     ModelError(russimp::RussimpError),
+    GltfError(gltf::Error),
     SceneError(String),
     MeshError(String),
     TextureError(String),
+    ImageCompareError(String),
     UnknownError(&'static str),
 }
 
@@ -23,12 +26,19 @@ impl From<image::ImageError> for Error {
     }
 }
 
+#[cfg(feature = "russimp")]
 impl From<russimp::RussimpError> for Error {
     fn from(s: russimp::RussimpError) -> Self {
         Error::ModelError(s)
     }
 }
 
+impl From<gltf::Error> for Error {
+    fn from(s: gltf::Error) -> Self {
+        Error::GltfError(s)
+    }
+}
+
 impl From<&'static str> for Error {
     fn from(s: &'static str) -> Self {
         Error::UnknownError(s)
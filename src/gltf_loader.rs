@@ -0,0 +1,599 @@
+use crate::animator::Animator;
+use crate::error::Error;
+use crate::error::Error::{ImageError, SceneError};
+use crate::gpu_context::GpuContext;
+use crate::material::Material;
+use crate::model_animation::{BoneData, ModelAnimation, NodeData};
+use crate::model_builder::ModelBuilder;
+use crate::model_mesh::{ModelMesh, ModelVertex, MorphTargetDelta, MorphTargets};
+use crate::node_animation::{KeyInterpolation, KeyPosition, KeyRotation, KeyScale, KeyWeights, NodeAnimation};
+use crate::texture_config::TextureType;
+use crate::transform::Transform;
+use glam::{vec2, Mat4, Quat, Vec3, Vec4};
+use gltf::animation::util::ReadOutputs;
+use gltf::animation::Interpolation;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use image::{DynamicImage, ImageBuffer};
+use log::debug;
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::rc::Rc;
+
+/// glTF key times are in seconds, they are converted to ticks at the same rate as assimp
+/// so animation clips work with either loader.
+pub const GLTF_TICKS_PER_SECOND: f32 = 1000.0;
+
+const GLTF_ROOT_NAME: &str = "gltf_root";
+
+impl ModelBuilder {
+    pub(crate) fn load_gltf_model(&mut self, context: &mut GpuContext) -> Result<Animator, Error> {
+        let (document, buffers, images) = gltf::import(&self.filepath)?;
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| SceneError(format!("glTF file has no scenes: {}", self.filepath)))?;
+
+        // a scene can have several root nodes so they are gathered under one
+        let mut root_node = NodeData {
+            name: Rc::from(GLTF_ROOT_NAME),
+            transform: Transform::IDENTITY,
+            children: vec![],
+            meshes: Rc::new(vec![]),
+        };
+
+        for node in scene.nodes() {
+            let node_data = self.process_gltf_node(context, &node, &buffers, &images)?;
+            root_node.children.push(node_data);
+        }
+
+        let animations = read_gltf_animations(&document, &buffers);
+
+        debug!("gltf animations: {}", animations.len());
+
+        Ok(Animator::from_node_data(root_node, animations, RefCell::new(self.bone_data_map.take())))
+    }
+
+    fn process_gltf_node(
+        &mut self,
+        context: &mut GpuContext,
+        node: &gltf::Node,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<NodeData, Error> {
+        let mut mesh_ids = vec![];
+
+        if let Some(mesh) = node.mesh() {
+            let bone_ids = match node.skin() {
+                Some(skin) => self.read_gltf_skin(&skin, buffers),
+                None => vec![],
+            };
+
+            let mesh_name = mesh.name().map(String::from).unwrap_or_else(|| format!("mesh_{}", mesh.index()));
+
+            // the node's morph weights replace the mesh's
+            let morph_weights = node.weights().or(mesh.weights()).unwrap_or_default();
+
+            for primitive in mesh.primitives() {
+                if primitive.mode() != Mode::Triangles {
+                    debug!("skipping primitive of mesh: {}  mode: {:?}", mesh_name, primitive.mode());
+                    continue;
+                }
+                let model_mesh = self.process_gltf_primitive(context, &primitive, &mesh_name, &bone_ids, morph_weights, buffers, images)?;
+                mesh_ids.push(model_mesh.id as u32);
+                self.meshes.push(model_mesh);
+            }
+        }
+
+        let mut node_data = NodeData {
+            name: gltf_node_name(node),
+            transform: gltf_node_transform(node),
+            children: vec![],
+            meshes: Rc::new(mesh_ids),
+        };
+
+        for child in node.children() {
+            let child_data = self.process_gltf_node(context, &child, buffers, images)?;
+            node_data.children.push(child_data);
+        }
+
+        Ok(node_data)
+    }
+
+    /// Adds the skin joints to the bone data map and returns the bone id of each joint.
+    fn read_gltf_skin(&mut self, skin: &gltf::Skin, buffers: &[gltf::buffer::Data]) -> Vec<i32> {
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let inverse_bind_matrices: Vec<Mat4> = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.map(|m| Mat4::from_cols_array_2d(&m)).collect(),
+            None => vec![],
+        };
+
+        let mut bone_data_map = self.bone_data_map.borrow_mut();
+
+        skin.joints()
+            .enumerate()
+            .map(|(joint_index, joint)| {
+                let name = gltf_node_name(&joint);
+
+                match bone_data_map.get(name.as_ref()) {
+                    Some(bone_data) => bone_data.bone_index,
+                    None => {
+                        let offset = inverse_bind_matrices.get(joint_index).copied().unwrap_or(Mat4::IDENTITY);
+                        let bone_data = BoneData::new(&name, self.bone_count, offset);
+                        bone_data_map.insert(name.to_string(), bone_data);
+                        self.bone_count += 1;
+                        self.bone_count - 1
+                    }
+                }
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_gltf_primitive(
+        &mut self,
+        context: &mut GpuContext,
+        primitive: &gltf::Primitive,
+        mesh_name: &str,
+        bone_ids: &[i32],
+        morph_weights: &[f32],
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<ModelMesh, Error> {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let mut vertices: Vec<ModelVertex> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|position| ModelVertex {
+                    position: Vec3::from_array(position),
+                    ..ModelVertex::new()
+                })
+                .collect(),
+            None => return Err(SceneError(format!("mesh: {} has no positions", mesh_name))),
+        };
+
+        if let Some(normals) = reader.read_normals() {
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = Vec3::from_array(normal);
+            }
+        }
+
+        if let Some(tex_coords) = reader.read_tex_coords(0) {
+            for (vertex, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+                vertex.uv = vec2(uv[0], uv[1]);
+            }
+        }
+
+        if let Some(tangents) = reader.read_tangents() {
+            for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                // w is the handedness of the bitangent
+                let tangent_xyz = Vec3::new(tangent[0], tangent[1], tangent[2]);
+                let normal = vertex.normal;
+                vertex.tangent = tangent_xyz;
+                vertex.bi_tangent = normal.cross(tangent_xyz) * tangent[3];
+            }
+        }
+
+        if let (Some(joints), Some(weights)) = (reader.read_joints(0), reader.read_weights(0)) {
+            for ((vertex, joints), weights) in vertices.iter_mut().zip(joints.into_u16()).zip(weights.into_f32()) {
+                for (joint, weight) in joints.iter().zip(weights.iter()) {
+                    if *weight > 0.0 {
+                        if let Some(bone_id) = bone_ids.get(*joint as usize) {
+                            vertex.set_bone_data(*bone_id, *weight);
+                        }
+                    }
+                }
+                vertex.normalize_bone_weights();
+            }
+        }
+
+        let mut morph_targets = MorphTargets {
+            default_weights: morph_weights.to_vec(),
+            ..MorphTargets::default()
+        };
+
+        for (positions, normals, tangents) in reader.read_morph_targets() {
+            let mut deltas = vec![MorphTargetDelta::default(); vertices.len()];
+
+            if let Some(positions) = positions {
+                for (delta, position) in deltas.iter_mut().zip(positions) {
+                    delta.position = Vec3::from_array(position).extend(0.0);
+                }
+            }
+            if let Some(normals) = normals {
+                for (delta, normal) in deltas.iter_mut().zip(normals) {
+                    delta.normal = Vec3::from_array(normal).extend(0.0);
+                }
+            }
+            if let Some(tangents) = tangents {
+                for (delta, tangent) in deltas.iter_mut().zip(tangents) {
+                    delta.tangent = Vec3::from_array(tangent).extend(0.0);
+                }
+            }
+
+            morph_targets.deltas.extend(deltas);
+            morph_targets.target_count += 1;
+        }
+
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+
+        let materials = if self.load_textures {
+            self.load_gltf_materials(context, &primitive.material(), images)
+        } else {
+            vec![]
+        };
+
+        debug!("mesh name: {}  morph targets: {}", mesh_name, morph_targets.target_count);
+
+        let mesh = ModelMesh::new(context, self.mesh_count, mesh_name, vertices, indices, materials, morph_targets);
+
+        self.mesh_count += 1;
+        Ok(mesh)
+    }
+
+    fn load_gltf_materials(&self, context: &mut GpuContext, material: &gltf::Material, images: &[gltf::image::Data]) -> Vec<Rc<Material>> {
+        let pbr = material.pbr_metallic_roughness();
+
+        let textures = [
+            (TextureType::Diffuse, pbr.base_color_texture().map(|info| info.texture())),
+            (TextureType::Roughness, pbr.metallic_roughness_texture().map(|info| info.texture())),
+            (TextureType::Normals, material.normal_texture().map(|info| info.texture())),
+            (TextureType::Emissive, material.emissive_texture().map(|info| info.texture())),
+            (TextureType::AmbientOcclusion, material.occlusion_texture().map(|info| info.texture())),
+        ];
+
+        let mut materials = vec![];
+
+        for (texture_type, texture) in textures {
+            if let Some(texture) = texture {
+                match self.load_or_get_gltf_material(context, &texture_type, &texture.source(), images) {
+                    Ok(material) => materials.push(material),
+                    Err(e) => debug!("{:?}", e),
+                }
+            }
+        }
+
+        materials
+    }
+
+    fn load_or_get_gltf_material(
+        &self,
+        context: &mut GpuContext,
+        texture_type: &TextureType,
+        image: &gltf::Image,
+        images: &[gltf::image::Data],
+    ) -> Result<Rc<Material>, Error> {
+        // external images are keyed by path so they share the cache with added textures
+        let texture_path: OsString = match image.source() {
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => self.directory.join(uri).into_os_string(),
+            _ => format!("{}#image{}", self.filepath, image.index()).into(),
+        };
+
+        if let Some(material) = self.get_cached_material(&texture_path, texture_type) {
+            return Ok(material);
+        }
+
+        let img = convert_gltf_image(&images[image.index()])?;
+
+        let material = Rc::new(Material::from_image(context, img, texture_path, &self.texture_config(texture_type)));
+        debug!("loaded texture: {:?}", material);
+
+        self.textures_cache.borrow_mut().push(material.clone());
+        Ok(material)
+    }
+}
+
+/// Reads every animation in the document, unnamed animations are named by their index. Channels are grouped by target node and
+/// properties without a channel are held at the node's rest transform.
+pub fn read_gltf_animations(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Vec<ModelAnimation> {
+    let mut model_animations = vec![];
+
+    for animation in document.animations() {
+        let mut node_animations: Vec<(Transform, NodeAnimation)> = vec![];
+        let mut duration = 0.0f32;
+
+        for channel in animation.channels() {
+            let node = channel.target().node();
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+
+            let Some(inputs) = reader.read_inputs() else {
+                continue;
+            };
+
+            let times: Vec<f32> = inputs.map(|time| time * GLTF_TICKS_PER_SECOND).collect();
+            duration = times.iter().fold(duration, |a, b| a.max(*b));
+
+            let interpolation = channel.sampler().interpolation();
+
+            let name = gltf_node_name(&node);
+            let index = match node_animations.iter().position(|(_, n)| n.name == name) {
+                Some(index) => index,
+                None => {
+                    let node_animation = NodeAnimation::from_keys(name, vec![], vec![], vec![]);
+                    node_animations.push((gltf_node_transform(&node), node_animation));
+                    node_animations.len() - 1
+                }
+            };
+            let node_animation = &mut node_animations[index].1;
+
+            match reader.read_outputs() {
+                Some(ReadOutputs::Translations(values)) => {
+                    node_animation.position_interpolation = key_interpolation(interpolation);
+                    node_animation.positions = keyframe_values(values.collect(), interpolation)
+                        .zip(&times)
+                        .map(|((in_tangent, value, out_tangent), time)| {
+                            KeyPosition::new(Vec3::from_array(value), *time).with_tangents(
+                                Vec3::from_array(in_tangent) / GLTF_TICKS_PER_SECOND,
+                                Vec3::from_array(out_tangent) / GLTF_TICKS_PER_SECOND,
+                            )
+                        })
+                        .collect();
+                }
+                Some(ReadOutputs::Rotations(values)) => {
+                    node_animation.rotation_interpolation = key_interpolation(interpolation);
+                    node_animation.rotations = keyframe_values(values.into_f32().collect(), interpolation)
+                        .zip(&times)
+                        .map(|((in_tangent, value, out_tangent), time)| {
+                            KeyRotation::new(Quat::from_array(value), *time).with_tangents(
+                                Vec4::from_array(in_tangent) / GLTF_TICKS_PER_SECOND,
+                                Vec4::from_array(out_tangent) / GLTF_TICKS_PER_SECOND,
+                            )
+                        })
+                        .collect();
+                }
+                Some(ReadOutputs::Scales(values)) => {
+                    node_animation.scale_interpolation = key_interpolation(interpolation);
+                    node_animation.scales = keyframe_values(values.collect(), interpolation)
+                        .zip(&times)
+                        .map(|((in_tangent, value, out_tangent), time)| {
+                            KeyScale::new(Vec3::from_array(value), *time).with_tangents(
+                                Vec3::from_array(in_tangent) / GLTF_TICKS_PER_SECOND,
+                                Vec3::from_array(out_tangent) / GLTF_TICKS_PER_SECOND,
+                            )
+                        })
+                        .collect();
+                }
+                Some(ReadOutputs::MorphTargetWeights(values)) => {
+                    let values: Vec<f32> = values.into_f32().collect();
+                    let values_per_key = match interpolation {
+                        Interpolation::CubicSpline => 3 * times.len(),
+                        _ => times.len(),
+                    };
+                    let target_count = values.len() / values_per_key.max(1);
+                    if target_count == 0 {
+                        continue;
+                    }
+
+                    let scale_tangents = |tangents: Vec<f32>| tangents.iter().map(|tangent| tangent / GLTF_TICKS_PER_SECOND).collect();
+
+                    node_animation.morph_weight_interpolation = key_interpolation(interpolation);
+                    node_animation.morph_weights =
+                        keyframe_values(values.chunks(target_count).map(<[f32]>::to_vec).collect(), interpolation)
+                            .zip(&times)
+                            .map(|((in_tangents, weights, out_tangents), time)| {
+                                KeyWeights::new(weights, *time).with_tangents(scale_tangents(in_tangents), scale_tangents(out_tangents))
+                            })
+                            .collect();
+                }
+                _ => debug!("skipping animation channel for node: {}", node_animation.name),
+            }
+        }
+
+        let node_animations: Vec<NodeAnimation> = node_animations
+            .into_iter()
+            .map(|(rest_transform, mut node_animation)| {
+                if node_animation.positions.is_empty() {
+                    node_animation.positions.push(KeyPosition::new(rest_transform.translation, 0.0));
+                }
+                if node_animation.rotations.is_empty() {
+                    node_animation.rotations.push(KeyRotation::new(rest_transform.rotation, 0.0));
+                }
+                if node_animation.scales.is_empty() {
+                    node_animation.scales.push(KeyScale::new(rest_transform.scale, 0.0));
+                }
+                node_animation
+            })
+            .collect();
+
+        debug!(
+            "gltf animation: {:?}  duration: {}  channels: {}",
+            animation.name(),
+            duration,
+            node_animations.len()
+        );
+
+        let name = match animation.name() {
+            Some(name) => Rc::from(name),
+            None => Rc::from(format!("animation_{}", animation.index())),
+        };
+
+        model_animations.push(ModelAnimation {
+            name,
+            duration,
+            ticks_per_second: GLTF_TICKS_PER_SECOND,
+            node_animations: RefCell::new(node_animations),
+        });
+    }
+
+    model_animations
+}
+
+/// The in tangent, value and out tangent of each key. Cubic spline samplers store all three,
+/// the tangents of other samplers are zero.
+fn keyframe_values<T: Clone + Default>(values: Vec<T>, interpolation: Interpolation) -> impl Iterator<Item = (T, T, T)> {
+    let values: Vec<(T, T, T)> = match interpolation {
+        Interpolation::CubicSpline => values
+            .chunks_exact(3)
+            .map(|triple| (triple[0].clone(), triple[1].clone(), triple[2].clone()))
+            .collect(),
+        _ => values.into_iter().map(|value| (T::default(), value, T::default())).collect(),
+    };
+    values.into_iter()
+}
+
+fn key_interpolation(interpolation: Interpolation) -> KeyInterpolation {
+    match interpolation {
+        Interpolation::Linear => KeyInterpolation::Linear,
+        Interpolation::Step => KeyInterpolation::Step,
+        Interpolation::CubicSpline => KeyInterpolation::CubicSpline,
+    }
+}
+
+/// Nodes without a name are named by their index so the animation channels and bones can refer to them.
+fn gltf_node_name(node: &gltf::Node) -> Rc<str> {
+    match node.name() {
+        Some(name) => Rc::from(name),
+        None => Rc::from(format!("node_{}", node.index())),
+    }
+}
+
+fn gltf_node_transform(node: &gltf::Node) -> Transform {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    Transform {
+        translation: Vec3::from_array(translation),
+        rotation: Quat::from_array(rotation),
+        scale: Vec3::from_array(scale),
+    }
+}
+
+fn convert_gltf_image(data: &gltf::image::Data) -> Result<DynamicImage, Error> {
+    let (width, height) = (data.width, data.height);
+    let pixels = data.pixels.clone();
+
+    let img = match data.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+        format => return Err(ImageError(format!("unsupported glTF image format: {:?}", format))),
+    };
+
+    img.ok_or_else(|| ImageError("glTF image data does not match its size".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gltf_loader::{read_gltf_animations, GLTF_TICKS_PER_SECOND};
+    use crate::node_animation::KeyInterpolation;
+    use glam::{Quat, Vec3};
+
+    // One node with a two key translation channel. The buffer holds the times 0.0 and 1.0
+    // followed by the translations (0, 0, 0) and (1, 2, 3).
+    const ANIMATED_NODE_GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [ 0 ] } ],
+        "nodes": [ { "name": "bone", "rotation": [ 0.0, 0.0, 0.0, 1.0 ], "scale": [ 2.0, 2.0, 2.0 ] } ],
+        "buffers": [ {
+            "byteLength": 32,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAQAAAQEA="
+        } ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 24 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [ 0.0 ], "max": [ 1.0 ] },
+            { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" }
+        ],
+        "animations": [ {
+            "name": "move",
+            "samplers": [ { "input": 0, "output": 1, "interpolation": "LINEAR" } ],
+            "channels": [ { "sampler": 0, "target": { "node": 0, "path": "translation" } } ]
+        } ]
+    }"#;
+
+    // The translation channel as a cubic spline. The buffer holds the times 0.0 and 1.0 followed by the
+    // in tangent, value and out tangent of each key, (9, 9, 9), (0, 0, 0), (2, 0, 0) then (2, 0, 0), (1, 0, 0), (7, 7, 7).
+    const CUBIC_SPLINE_GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [ 0 ] } ],
+        "nodes": [ { "name": "bone" } ],
+        "buffers": [ {
+            "byteLength": 80,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAgD8AABBBAAAQQQAAEEEAAAAAAAAAAAAAAAAAAABAAAAAAAAAAAAAAABAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAOBAAADgQAAA4EA="
+        } ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 72 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [ 0.0 ], "max": [ 1.0 ] },
+            { "bufferView": 1, "componentType": 5126, "count": 6, "type": "VEC3" }
+        ],
+        "animations": [ {
+            "name": "move",
+            "samplers": [ { "input": 0, "output": 1, "interpolation": "CUBICSPLINE" } ],
+            "channels": [ { "sampler": 0, "target": { "node": 0, "path": "translation" } } ]
+        } ]
+    }"#;
+
+    #[test]
+    fn test_read_gltf_animations() {
+        let (document, buffers, _images) = gltf::import_slice(ANIMATED_NODE_GLTF.as_bytes()).unwrap();
+
+        let animations = read_gltf_animations(&document, &buffers);
+        assert_eq!(animations.len(), 1);
+
+        let animation = &animations[0];
+        assert_eq!(animation.name.as_ref(), "move");
+        assert_eq!(animation.duration, GLTF_TICKS_PER_SECOND);
+        assert_eq!(animation.ticks_per_second, GLTF_TICKS_PER_SECOND);
+
+        let node_animations = animation.node_animations.borrow();
+        assert_eq!(node_animations.len(), 1);
+        assert_eq!(node_animations[0].name.as_ref(), "bone");
+        assert_eq!(node_animations[0].positions.len(), 2);
+
+        // rotation and scale have no channel so they hold the rest transform
+        let transform = node_animations[0].get_animation_transform(500.0);
+        assert!(transform.translation.abs_diff_eq(Vec3::new(0.5, 1.0, 1.5), 1e-5));
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+        assert_eq!(transform.scale, Vec3::splat(2.0));
+    }
+
+    #[test]
+    fn test_read_step_channel() {
+        let gltf = ANIMATED_NODE_GLTF.replace("LINEAR", "STEP");
+        let (document, buffers, _images) = gltf::import_slice(gltf.as_bytes()).unwrap();
+
+        let animations = read_gltf_animations(&document, &buffers);
+        let node_animations = animations[0].node_animations.borrow();
+        let node_animation = &node_animations[0];
+        assert_eq!(node_animation.position_interpolation, KeyInterpolation::Step);
+
+        // each key holds until the next
+        assert_eq!(node_animation.get_animation_transform(999.0).translation, Vec3::ZERO);
+        assert_eq!(node_animation.get_animation_transform(1000.0).translation, Vec3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_read_cubic_spline_channel() {
+        let (document, buffers, _images) = gltf::import_slice(CUBIC_SPLINE_GLTF.as_bytes()).unwrap();
+
+        let animations = read_gltf_animations(&document, &buffers);
+        let node_animations = animations[0].node_animations.borrow();
+        let node_animation = &node_animations[0];
+        assert_eq!(node_animation.position_interpolation, KeyInterpolation::CubicSpline);
+
+        // the values are split from their tangents, which are per tick rather than per second
+        let positions = &node_animation.positions;
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].position, Vec3::ZERO);
+        assert_eq!(positions[1].position, Vec3::X);
+        assert!(positions[0].in_tangent.abs_diff_eq(Vec3::splat(9.0 / GLTF_TICKS_PER_SECOND), 1e-7));
+        assert!(positions[0].out_tangent.abs_diff_eq(Vec3::X * 2.0 / GLTF_TICKS_PER_SECOND, 1e-7));
+        assert!(positions[1].in_tangent.abs_diff_eq(Vec3::X * 2.0 / GLTF_TICKS_PER_SECOND, 1e-7));
+        assert!(positions[1].out_tangent.abs_diff_eq(Vec3::splat(7.0 / GLTF_TICKS_PER_SECOND), 1e-7));
+
+        // a quarter of the way along the tangents pull the spline ahead of the 0.15625 it has without them
+        let translation = node_animation.get_animation_transform(250.0).translation;
+        assert!(translation.abs_diff_eq(Vec3::new(0.34375, 0.0, 0.0), 1e-5));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::Error;
+use crate::error::Error::ImageCompareError;
+use image::{Rgba, RgbaImage};
+use std::fs;
+use std::path::Path;
+
+/// Set to rewrite the reference images with the current render output.
+pub const BLESS_ENV_VAR: &str = "SPARK_GAP_BLESS";
+
+// Largest possible YIQ delta between two colors.
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompareConfig {
+    /// Per channel difference that is always accepted, to absorb rounding between drivers.
+    pub channel_tolerance: u8,
+    /// Perceptual color difference, 0.0 to 1.0, above which a pixel counts as different.
+    pub perceptual_threshold: f32,
+    /// Fraction of the pixels that may differ before the comparison fails.
+    pub max_different_ratio: f32,
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        CompareConfig {
+            channel_tolerance: 2,
+            perceptual_threshold: 0.1,
+            max_different_ratio: 0.001,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageDiff {
+    pub different_pixels: u32,
+    pub total_pixels: u32,
+    pub max_channel_delta: u8,
+    pub max_perceptual_delta: f32,
+    /// Faded copy of the expected image with the different pixels in red.
+    pub diff_image: RgbaImage,
+}
+
+impl ImageDiff {
+    pub fn different_ratio(&self) -> f32 {
+        self.different_pixels as f32 / self.total_pixels.max(1) as f32
+    }
+
+    pub fn passes(&self, config: &CompareConfig) -> bool {
+        self.different_ratio() <= config.max_different_ratio
+    }
+}
+
+pub fn compare_images(expected: &RgbaImage, actual: &RgbaImage, config: &CompareConfig) -> Result<ImageDiff, Error> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(ImageCompareError(format!(
+            "image size mismatch, expected: {:?}  actual: {:?}",
+            expected.dimensions(),
+            actual.dimensions()
+        )));
+    }
+
+    let (width, height) = expected.dimensions();
+    let mut diff_image = RgbaImage::new(width, height);
+    let mut different_pixels = 0;
+    let mut max_channel_delta = 0;
+    let mut max_perceptual_delta = 0.0f32;
+
+    for ((expected_pixel, actual_pixel), diff_pixel) in expected.pixels().zip(actual.pixels()).zip(diff_image.pixels_mut()) {
+        let channel_delta = expected_pixel
+            .0
+            .iter()
+            .zip(actual_pixel.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+
+        max_channel_delta = max_channel_delta.max(channel_delta);
+
+        let is_different = if channel_delta <= config.channel_tolerance {
+            false
+        } else {
+            let delta = perceptual_delta(*expected_pixel, *actual_pixel);
+            max_perceptual_delta = max_perceptual_delta.max(delta);
+            delta > config.perceptual_threshold
+        };
+
+        *diff_pixel = if is_different {
+            different_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            faded_gray(*expected_pixel)
+        };
+    }
+
+    Ok(ImageDiff {
+        different_pixels,
+        total_pixels: width * height,
+        max_channel_delta,
+        max_perceptual_delta,
+        diff_image,
+    })
+}
+
+/// Perceptual difference between two colors in YIQ space, scaled so the largest possible
+/// difference is 1.0. Based on the metric used by pixelmatch.
+pub fn perceptual_delta(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let (y1, i1, q1) = rgb_to_yiq(a);
+    let (y2, i2, q2) = rgb_to_yiq(b);
+
+    let y = y1 - y2;
+    let i = i1 - i2;
+    let q = q1 - q2;
+
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    (delta / MAX_YIQ_DELTA).sqrt().min(1.0)
+}
+
+/// Compares `actual` with the png at `reference_path`. With the `SPARK_GAP_BLESS` environment variable
+/// set, `actual` is written as the new reference instead. A missing reference is an error, so a test
+/// can't pass by recording its own output. On failure the actual and diff images are written to `output_dir`.
+pub fn check_golden(actual: &RgbaImage, reference_path: &Path, output_dir: &Path, config: &CompareConfig) -> Result<ImageDiff, Error> {
+    if std::env::var_os(BLESS_ENV_VAR).is_some() {
+        if let Some(parent) = reference_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        actual.save_with_format(reference_path, image::ImageFormat::Png)?;
+        return compare_images(actual, actual, config);
+    }
+
+    let name = reference_path.file_stem().unwrap_or_default().to_string_lossy();
+    let actual_path = output_dir.join(format!("{}.actual.png", name));
+
+    if !reference_path.is_file() {
+        fs::create_dir_all(output_dir)?;
+        actual.save_with_format(&actual_path, image::ImageFormat::Png)?;
+
+        return Err(ImageCompareError(format!(
+            "reference image {:?} not found, set {} to record it  actual image: {:?}",
+            reference_path, BLESS_ENV_VAR, actual_path
+        )));
+    }
+
+    let expected = image::open(reference_path)?.to_rgba8();
+    let diff = compare_images(&expected, actual, config)?;
+
+    if !diff.passes(config) {
+        fs::create_dir_all(output_dir)?;
+
+        let diff_path = output_dir.join(format!("{}.diff.png", name));
+        actual.save_with_format(&actual_path, image::ImageFormat::Png)?;
+        diff.diff_image.save_with_format(&diff_path, image::ImageFormat::Png)?;
+
+        return Err(ImageCompareError(format!(
+            "{} of {} pixels differ from {:?}  max channel delta: {}  max perceptual delta: {:.3}  diff image: {:?}",
+            diff.different_pixels, diff.total_pixels, reference_path, diff.max_channel_delta, diff.max_perceptual_delta, diff_path
+        )));
+    }
+
+    Ok(diff)
+}
+
+fn rgb_to_yiq(pixel: Rgba<u8>) -> (f32, f32, f32) {
+    // blend with white so transparent pixels compare by how they would look
+    let alpha = pixel[3] as f32 / 255.0;
+    let r = 255.0 + (pixel[0] as f32 - 255.0) * alpha;
+    let g = 255.0 + (pixel[1] as f32 - 255.0) * alpha;
+    let b = 255.0 + (pixel[2] as f32 - 255.0) * alpha;
+
+    let y = r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_2;
+    let i = r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9;
+    let q = r * 0.211_470_2 - g * 0.522_617_1 + b * 0.311_146_9;
+    (y, i, q)
+}
+
+fn faded_gray(pixel: Rgba<u8>) -> Rgba<u8> {
+    let (y, _, _) = rgb_to_yiq(pixel);
+    let value = (255.0 + (y - 255.0) * 0.1) as u8;
+    Rgba([value, value, value, 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::golden_image::{check_golden, compare_images, perceptual_delta, CompareConfig, BLESS_ENV_VAR};
+    use image::{Rgba, RgbaImage};
+
+    fn checker_image() -> RgbaImage {
+        RgbaImage::from_fn(32, 32, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                Rgba([200, 40, 40, 255])
+            } else {
+                Rgba([20, 20, 160, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn test_identical_images() {
+        let image = checker_image();
+        let diff = compare_images(&image, &image, &CompareConfig::default()).unwrap();
+
+        assert_eq!(diff.different_pixels, 0);
+        assert_eq!(diff.max_channel_delta, 0);
+        assert!(diff.passes(&CompareConfig::default()));
+    }
+
+    #[test]
+    fn test_small_differences_within_tolerance() {
+        let expected = checker_image();
+        let mut actual = expected.clone();
+        for pixel in actual.pixels_mut() {
+            pixel[0] = pixel[0].saturating_add(2);
+        }
+
+        let diff = compare_images(&expected, &actual, &CompareConfig::default()).unwrap();
+
+        assert_eq!(diff.different_pixels, 0);
+        assert_eq!(diff.max_channel_delta, 2);
+    }
+
+    #[test]
+    fn test_changed_block_fails() {
+        let expected = checker_image();
+        let mut actual = expected.clone();
+        for y in 0..8 {
+            for x in 0..8 {
+                actual.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+
+        let config = CompareConfig::default();
+        let diff = compare_images(&expected, &actual, &config).unwrap();
+
+        assert_eq!(diff.different_pixels, 64);
+        assert!(!diff.passes(&config));
+        assert_eq!(*diff.diff_image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let expected = checker_image();
+        let actual = RgbaImage::new(16, 16);
+
+        assert!(compare_images(&expected, &actual, &CompareConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_perceptual_delta_range() {
+        let black = Rgba([0, 0, 0, 255]);
+        let white = Rgba([255, 255, 255, 255]);
+
+        assert!(perceptual_delta(black, black) < 1e-6);
+        assert!(perceptual_delta(black, white) > 0.9);
+        assert!(perceptual_delta(black, white) <= 1.0);
+    }
+
+    #[test]
+    fn test_missing_reference_fails() {
+        if std::env::var_os(BLESS_ENV_VAR).is_some() {
+            return;
+        }
+
+        let output_dir = std::env::temp_dir().join(format!("spark_gap_golden_{}", std::process::id()));
+        let reference_path = output_dir.join("missing").join("checker.png");
+
+        let result = check_golden(&checker_image(), &reference_path, &output_dir, &CompareConfig::default());
+
+        assert!(result.is_err());
+        assert!(!reference_path.exists());
+        assert!(output_dir.join("checker.actual.png").is_file());
+        let _ = std::fs::remove_dir_all(&output_dir);
+    }
+}
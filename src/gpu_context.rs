@@ -1,13 +1,21 @@
+use crate::error::Error;
+use crate::error::Error::TextureError;
 use crate::hash_map::HashMap;
+use crate::texture::{create_depth_texture, Texture};
+use image::RgbaImage;
 use log::debug;
+use std::path::Path;
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use wgpu::BindGroupLayout;
 use winit::window::Window;
 
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
 pub struct GpuContext {
-    pub window: Arc<Window>,
-    pub surface: wgpu::Surface<'static>,
+    pub window: Option<Arc<Window>>,
+    pub surface: Option<wgpu::Surface<'static>>,
+    pub offscreen_target: Option<OffscreenTarget>,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
@@ -16,6 +24,39 @@ pub struct GpuContext {
     pub bind_layout_cache: HashMap<String, Rc<BindGroupLayout>>,
 }
 
+/// Color and depth textures used in place of the swap chain by a headless context.
+pub struct OffscreenTarget {
+    pub color_texture: wgpu::Texture,
+    pub depth_texture: Texture,
+}
+
+/// The texture to render into for the current frame, either acquired from the surface
+/// or the offscreen color target of a headless context.
+pub enum Frame<'a> {
+    Surface(wgpu::SurfaceTexture),
+    Offscreen(&'a wgpu::Texture),
+}
+
+impl Frame<'_> {
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Frame::Surface(surface_texture) => &surface_texture.texture,
+            Frame::Offscreen(texture) => texture,
+        }
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.texture().create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Presents a surface frame. Offscreen frames stay in the color target until the next render.
+    pub fn present(self) {
+        if let Frame::Surface(surface_texture) = self {
+            surface_texture.present();
+        }
+    }
+}
+
 impl Drop for GpuContext {
     fn drop(&mut self) {
         debug!("Context dropped")
@@ -41,23 +82,7 @@ impl GpuContext {
             .await
             .expect("Failed to find an appropriate adapter");
 
-        let desired_max_bind_groups = 8;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER,
-                    // required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
-                    required_limits: wgpu::Limits {
-                        max_bind_groups: desired_max_bind_groups,
-                        ..wgpu::Limits::default() // Fill in other limits with default values
-                    },
-                },
-                None,
-            )
-            .await
-            .expect("Failed to create device");
+        let (device, queue) = request_device(&adapter).await;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -71,8 +96,11 @@ impl GpuContext {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
+        // Copying out of the frame is needed for screenshots, but not every surface supports it.
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC);
+
         let mut config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -87,8 +115,9 @@ impl GpuContext {
         surface.configure(&device, &config);
 
         Self {
-            window,
-            surface,
+            window: Some(window),
+            surface: Some(surface),
+            offscreen_target: None,
             adapter,
             device,
             queue,
@@ -98,13 +127,246 @@ impl GpuContext {
         }
     }
 
+    /// Creates a context without a window or surface. Frames are rendered into an offscreen
+    /// color target of the given size. Set `force_fallback_adapter` to run on a software
+    /// adapter such as lavapipe or llvmpipe when no GPU is available.
+    pub async fn new_headless(width: u32, height: u32, force_fallback_adapter: bool) -> GpuContext {
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+
+        let instance = wgpu::Instance::default();
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter,
+            })
+            .await
+            .expect("Failed to find an appropriate adapter");
+
+        let (device, queue) = request_device(&adapter).await;
+
+        // Not used to configure a surface, but keeps the format and size in the same place
+        // for the renderers whether or not there is a window.
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![OFFSCREEN_FORMAT],
+        };
+
+        let mut context = Self {
+            window: None,
+            surface: None,
+            offscreen_target: None,
+            adapter,
+            device,
+            queue,
+            config,
+            size,
+            bind_layout_cache: HashMap::new(),
+        };
+
+        context.offscreen_target = Some(OffscreenTarget::new(&context));
+        context
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Whether vertex shaders can read storage buffers, which downlevel devices such as WebGL can't.
+    pub fn supports_vertex_storage(&self) -> bool {
+        let downlevel = self.adapter.get_downlevel_capabilities();
+        downlevel.flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE) && self.device.limits().max_storage_buffers_per_shader_stage > 0
+    }
+
+    /// Whether the model's meshes can be skinned by a compute pass, which also needs the palettes in storage buffers.
+    pub fn supports_compute_skinning(&self) -> bool {
+        let downlevel = self.adapter.get_downlevel_capabilities();
+        downlevel.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            && self.supports_vertex_storage()
+            && self.device.limits().max_storage_buffers_per_shader_stage >= 7
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size.width = new_size.width.max(1);
         self.size.height = new_size.height.max(1);
         self.config.width = self.size.width;
         self.config.height = self.size.height;
-        self.surface.configure(&self.device, &self.config);
+
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.config),
+            None => self.offscreen_target = Some(OffscreenTarget::new(self)),
+        }
+    }
+
+    /// Gets the texture to render the next frame into.
+    pub fn get_current_frame(&self) -> Frame<'_> {
+        match (&self.surface, &self.offscreen_target) {
+            (Some(surface), _) => Frame::Surface(
+                surface
+                    .get_current_texture()
+                    .expect("Failed to acquire next swap chain texture"),
+            ),
+            (None, Some(target)) => Frame::Offscreen(&target.color_texture),
+            (None, None) => panic!("GpuContext has neither a surface nor an offscreen target"),
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Reads back the frame's color texture. Surface frames must be captured before they are presented.
+    pub fn capture_frame(&self, frame: &Frame) -> Result<RgbaImage, Error> {
+        self.read_texture(frame.texture())
     }
+
+    /// Reads back the frame's color texture and writes it to `path` as a png.
+    pub fn save_frame(&self, frame: &Frame, path: impl AsRef<Path>) -> Result<(), Error> {
+        let image = self.capture_frame(frame)?;
+        image.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+
+    /// Reads back the offscreen color target of a headless context after rendering.
+    pub fn capture_offscreen_target(&self) -> Result<RgbaImage, Error> {
+        match &self.offscreen_target {
+            Some(target) => self.read_texture(&target.color_texture),
+            None => Err(TextureError("context has no offscreen target".to_string())),
+        }
+    }
+
+    /// Copies a 2d rgba8 or bgra8 texture into a staging buffer and returns it as an image.
+    /// The texture must have been created with `COPY_SRC` usage. sRGB formats are returned
+    /// as stored, which is already the encoding png expects.
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Result<RgbaImage, Error> {
+        let swap_red_blue = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(TextureError(format!("unsupported texture format for read back: {:?}", format))),
+        };
+
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(TextureError("texture was not created with COPY_SRC usage".to_string()));
+        }
+
+        let width = texture.width();
+        let height = texture.height();
+
+        // Rows in a texture to buffer copy must be aligned to 256 bytes.
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = wgpu::util::align_to(unpadded_bytes_per_row, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read back staging buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("read back") });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        match receiver.recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(TextureError(format!("failed to map read back buffer: {:?}", e))),
+            Err(e) => return Err(TextureError(format!("failed to map read back buffer: {:?}", e))),
+        }
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let padded_data = buffer_slice.get_mapped_range();
+            for row in padded_data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        staging_buffer.unmap();
+
+        if swap_red_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(width, height, pixels).ok_or(TextureError("read back buffer size mismatch".to_string()))
+    }
+}
+
+impl OffscreenTarget {
+    pub fn new(context: &GpuContext) -> Self {
+        let color_texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen color texture"),
+            size: wgpu::Extent3d {
+                width: context.config.width,
+                height: context.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.config.format,
+            usage: context.config.usage | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &context.config.view_formats,
+        });
+
+        let depth_texture = create_depth_texture(context);
+
+        OffscreenTarget {
+            color_texture,
+            depth_texture,
+        }
+    }
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    let desired_max_bind_groups = 8;
+
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER,
+                // required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                required_limits: wgpu::Limits {
+                    max_bind_groups: desired_max_bind_groups,
+                    ..wgpu::Limits::default() // Fill in other limits with default values
+                },
+            },
+            None,
+        )
+        .await
+        .expect("Failed to create device")
 }
 
 pub fn get_or_create_bind_group_layout(
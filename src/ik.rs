@@ -0,0 +1,291 @@
+use crate::pose::{global_pose, PoseNode};
+use crate::transform::Transform;
+use glam::{Quat, Vec3};
+use std::rc::Rc;
+
+/// Bends a chain of three nodes, such as thigh, knee and foot, so the end reaches the target.
+/// The middle joint bends toward the pole. Positions are in the model's space, the space of the node transforms.
+#[derive(Debug, Clone)]
+pub struct TwoBoneIk {
+    pub upper: Rc<str>,
+    pub middle: Rc<str>,
+    pub end: Rc<str>,
+    pub target: Vec3,
+    pub pole: Vec3,
+    /// Blends from the animated pose at 0.0 to the solved pose at 1.0.
+    pub weight: f32,
+}
+
+impl TwoBoneIk {
+    pub fn new(upper: &str, middle: &str, end: &str, target: Vec3, pole: Vec3) -> Self {
+        TwoBoneIk {
+            upper: Rc::from(upper),
+            middle: Rc::from(middle),
+            end: Rc::from(end),
+            target,
+            pole,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Turns a chain of nodes, such as spine, neck and head, so the aim axis of the last node points at the target.
+/// The turn is spread along the chain, with each node limited to `max_angle` radians.
+#[derive(Debug, Clone)]
+pub struct LookAtIk {
+    /// From the top of the chain down to the node that aims, each node a descendant of the one before.
+    pub chain: Vec<Rc<str>>,
+    /// The direction in the last node's space that points at the target.
+    pub aim_axis: Vec3,
+    pub target: Vec3,
+    pub max_angle: f32,
+    /// Blends from the animated pose at 0.0 to the solved pose at 1.0.
+    pub weight: f32,
+}
+
+impl LookAtIk {
+    pub fn new(chain: &[&str], aim_axis: Vec3, target: Vec3) -> Self {
+        LookAtIk {
+            chain: chain.iter().map(|name| Rc::from(*name)).collect(),
+            aim_axis: aim_axis.normalize(),
+            target,
+            max_angle: std::f32::consts::PI,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_max_angle(mut self, max_angle: f32) -> Self {
+        self.max_angle = max_angle;
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum IkConstraint {
+    TwoBone(TwoBoneIk),
+    LookAt(LookAtIk),
+}
+
+impl IkConstraint {
+    /// Changes the local rotations of the constrained nodes. Constraints naming missing nodes are skipped.
+    pub fn solve(&self, pose_nodes: &[PoseNode], local_pose: &mut [Transform], root_transform: Transform) {
+        match self {
+            IkConstraint::TwoBone(two_bone) => solve_two_bone(two_bone, pose_nodes, local_pose, root_transform),
+            IkConstraint::LookAt(look_at) => solve_look_at(look_at, pose_nodes, local_pose, root_transform),
+        }
+    }
+}
+
+fn node_index(pose_nodes: &[PoseNode], name: &str) -> Option<usize> {
+    pose_nodes.iter().position(|pose_node| pose_node.name.as_ref() == name)
+}
+
+fn clamped_angle(a: Vec3, b: Vec3) -> f32 {
+    a.normalize_or_zero().dot(b.normalize_or_zero()).clamp(-1.0, 1.0).acos()
+}
+
+/// The analytic solution, the upper joint turns the chain toward the target after both joints
+/// set the angle that gives the distance to the target.
+fn solve_two_bone(two_bone: &TwoBoneIk, pose_nodes: &[PoseNode], local_pose: &mut [Transform], root_transform: Transform) {
+    let (Some(upper), Some(middle), Some(end)) = (
+        node_index(pose_nodes, &two_bone.upper),
+        node_index(pose_nodes, &two_bone.middle),
+        node_index(pose_nodes, &two_bone.end),
+    ) else {
+        return;
+    };
+
+    let globals = global_pose(pose_nodes, local_pose, root_transform);
+
+    let a = globals[upper].translation;
+    let b = globals[middle].translation;
+    let c = globals[end].translation;
+    let t = two_bone.target;
+
+    let upper_length = (b - a).length();
+    let lower_length = (c - b).length();
+    if upper_length <= f32::EPSILON || lower_length <= f32::EPSILON {
+        return;
+    }
+
+    // keep just short of full reach, where the bend direction is lost
+    let epsilon = 1e-4 * (upper_length + lower_length);
+    let target_length = (t - a).length().clamp(epsilon, upper_length + lower_length - epsilon);
+
+    let current_upper_angle = clamped_angle(c - a, b - a);
+    let current_middle_angle = clamped_angle(a - b, c - b);
+    let current_target_angle = clamped_angle(c - a, t - a);
+
+    let upper_angle = ((lower_length * lower_length - upper_length * upper_length - target_length * target_length)
+        / (-2.0 * upper_length * target_length))
+        .clamp(-1.0, 1.0)
+        .acos();
+    let middle_angle = ((target_length * target_length - upper_length * upper_length - lower_length * lower_length)
+        / (-2.0 * upper_length * lower_length))
+        .clamp(-1.0, 1.0)
+        .acos();
+
+    let bend_axis = (two_bone.pole - a).cross(c - a).normalize_or_zero();
+    let target_axis = (c - a).cross(t - a).normalize_or_zero();
+    if bend_axis == Vec3::ZERO {
+        return;
+    }
+
+    let upper_rotation = globals[upper].rotation.inverse();
+    let middle_rotation = globals[middle].rotation.inverse();
+
+    let upper_bend = Quat::from_axis_angle(upper_rotation * bend_axis, current_upper_angle - upper_angle);
+    let middle_bend = Quat::from_axis_angle(middle_rotation * bend_axis, current_middle_angle - middle_angle);
+    let upper_turn = if target_axis == Vec3::ZERO {
+        Quat::IDENTITY
+    } else {
+        Quat::from_axis_angle(upper_rotation * target_axis, current_target_angle)
+    };
+
+    let upper_local = local_pose[upper].rotation;
+    let middle_local = local_pose[middle].rotation;
+
+    let upper_solved = (upper_local * upper_turn * upper_bend).normalize();
+    let middle_solved = (middle_local * middle_bend).normalize();
+
+    local_pose[upper].rotation = upper_local.slerp(upper_solved, two_bone.weight.clamp(0.0, 1.0));
+    local_pose[middle].rotation = middle_local.slerp(middle_solved, two_bone.weight.clamp(0.0, 1.0));
+}
+
+/// Turns each node of the chain by its share of the remaining turn, from the top down.
+fn solve_look_at(look_at: &LookAtIk, pose_nodes: &[PoseNode], local_pose: &mut [Transform], root_transform: Transform) {
+    let Some(chain) = look_at
+        .chain
+        .iter()
+        .map(|name| node_index(pose_nodes, name))
+        .collect::<Option<Vec<usize>>>()
+    else {
+        return;
+    };
+
+    let Some(&aim_node) = chain.last() else {
+        return;
+    };
+
+    let weight = look_at.weight.clamp(0.0, 1.0);
+
+    for (position, &index) in chain.iter().enumerate() {
+        let globals = global_pose(pose_nodes, local_pose, root_transform);
+
+        let aim_global = globals[aim_node];
+        let aim_direction = (aim_global.rotation * look_at.aim_axis).normalize_or_zero();
+        let target_direction = (look_at.target - aim_global.translation).normalize_or_zero();
+        if aim_direction == Vec3::ZERO || target_direction == Vec3::ZERO {
+            return;
+        }
+
+        let share = weight / (chain.len() - position) as f32;
+        let (axis, angle) = Quat::from_rotation_arc(aim_direction, target_direction).to_axis_angle();
+        let turn = Quat::from_axis_angle(axis, (angle * share).min(look_at.max_angle));
+
+        // the global turn moved into the node's local space
+        let node_rotation = globals[index].rotation;
+        let parent_rotation = match pose_nodes[index].parent {
+            Some(parent) => globals[parent].rotation,
+            None => root_transform.rotation,
+        };
+
+        local_pose[index].rotation = (parent_rotation.inverse() * turn * node_rotation).normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ik::{IkConstraint, LookAtIk, TwoBoneIk};
+    use crate::model_animation::NodeData;
+    use crate::pose::{flatten_nodes, global_pose};
+    use crate::transform::Transform;
+    use glam::Vec3;
+    use std::rc::Rc;
+
+    /// A leg standing straight down from the origin, a hip with a thigh and shin of length 1.
+    fn leg() -> NodeData {
+        let node = |name: &str, transform: Transform, children: Vec<NodeData>| NodeData {
+            name: Rc::from(name),
+            transform,
+            children,
+            meshes: Rc::new(vec![]),
+        };
+
+        node(
+            "hip",
+            Transform::IDENTITY,
+            vec![node(
+                "knee",
+                Transform::from_xyz(0.0, -1.0, 0.0),
+                vec![node("foot", Transform::from_xyz(0.0, -1.0, 0.0), vec![])],
+            )],
+        )
+    }
+
+    #[test]
+    fn test_two_bone_reaches_target() {
+        let pose_nodes = flatten_nodes(&leg());
+        let mut local_pose: Vec<Transform> = pose_nodes.iter().map(|n| n.transform).collect();
+
+        let target = Vec3::new(0.3, -1.5, 0.2);
+        let constraint = IkConstraint::TwoBone(TwoBoneIk::new("hip", "knee", "foot", target, Vec3::new(0.0, -1.0, 1.0)));
+        constraint.solve(&pose_nodes, &mut local_pose, Transform::IDENTITY);
+
+        let globals = global_pose(&pose_nodes, &local_pose, Transform::IDENTITY);
+        assert!(globals[2].translation.abs_diff_eq(target, 1e-4));
+
+        // the bones keep their lengths and the knee bends toward the pole
+        assert!((globals[1].translation.length() - 1.0).abs() < 1e-4);
+        assert!(globals[1].translation.z > 0.2);
+    }
+
+    #[test]
+    fn test_two_bone_out_of_reach() {
+        let pose_nodes = flatten_nodes(&leg());
+        let mut local_pose: Vec<Transform> = pose_nodes.iter().map(|n| n.transform).collect();
+
+        let target = Vec3::new(3.0, 0.0, 0.0);
+        let constraint = IkConstraint::TwoBone(TwoBoneIk::new("hip", "knee", "foot", target, Vec3::Z));
+        constraint.solve(&pose_nodes, &mut local_pose, Transform::IDENTITY);
+
+        // the leg points straight at the target
+        let globals = global_pose(&pose_nodes, &local_pose, Transform::IDENTITY);
+        assert!(globals[2].translation.abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-2));
+    }
+
+    #[test]
+    fn test_look_at_limits() {
+        let pose_nodes = flatten_nodes(&leg());
+        let rest_pose: Vec<Transform> = pose_nodes.iter().map(|n| n.transform).collect();
+
+        // the foot's aim axis points forward, the target is to the side
+        let target = Vec3::new(10.0, -2.0, 0.0);
+        let look_at = LookAtIk::new(&["knee", "foot"], Vec3::Z, target);
+
+        let mut local_pose = rest_pose.clone();
+        IkConstraint::LookAt(look_at.clone()).solve(&pose_nodes, &mut local_pose, Transform::IDENTITY);
+
+        let globals = global_pose(&pose_nodes, &local_pose, Transform::IDENTITY);
+        let aim = globals[2].rotation * Vec3::Z;
+        assert!(aim.abs_diff_eq((target - globals[2].translation).normalize(), 1e-4));
+
+        // limited to 0.2 radians per node the aim only turns 0.4
+        let mut local_pose = rest_pose;
+        IkConstraint::LookAt(look_at.with_max_angle(0.2)).solve(&pose_nodes, &mut local_pose, Transform::IDENTITY);
+
+        let globals = global_pose(&pose_nodes, &local_pose, Transform::IDENTITY);
+        let aim = globals[2].rotation * Vec3::Z;
+        assert!((aim.angle_between(Vec3::Z) - 0.4).abs() < 1e-3);
+    }
+}
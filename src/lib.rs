@@ -5,14 +5,21 @@ use std::ffi::c_void;
 use std::mem;
 use std::os::raw;
 
+pub mod animation_state_machine;
 pub mod animator;
+pub mod baked_animation;
+pub mod blend_space;
 pub mod buffers;
 pub mod camera;
+pub mod dual_quat;
 pub mod error;
 pub mod frame_counter;
+pub mod gltf_loader;
+pub mod golden_image;
 pub mod gpu_context;
 pub mod hash_any;
 pub mod hash_map;
+pub mod ik;
 pub mod input;
 pub mod material;
 pub mod math;
@@ -21,6 +28,10 @@ pub mod model_animation;
 pub mod model_builder;
 pub mod model_mesh;
 pub mod node_animation;
+pub mod pose;
+pub mod pose_cache;
+pub mod retarget;
+pub mod skinning;
 pub mod small_mesh;
 pub mod texture;
 pub mod texture_config;
@@ -2,7 +2,7 @@ use crate::error::Error;
 use crate::error::Error::ImageError;
 use crate::gpu_context::GpuContext;
 use crate::texture_config::{TextureConfig, TextureFilter, TextureType, TextureWrap};
-use image::GenericImageView;
+use image::{DynamicImage, GenericImageView};
 use std::ffi::OsString;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -27,14 +27,29 @@ impl Material {
         let file_path = file_path.into();
         load_texture(context, &file_path, texture_config)
     }
+
+    /// Creates the material from an already decoded image, such as one embedded in a glTF file.
+    /// The `texture_path` is only used as the key for caching.
+    pub fn from_image(
+        context: &mut GpuContext,
+        img: DynamicImage,
+        texture_path: impl Into<OsString>,
+        texture_config: &TextureConfig,
+    ) -> Material {
+        create_material(context, img, texture_path.into(), texture_config)
+    }
 }
 
 pub fn load_texture(context: &mut GpuContext, texture_path: &PathBuf, texture_config: &TextureConfig) -> Result<Material, Error> {
-    let mut img = match image::open(texture_path) {
+    let img = match image::open(texture_path) {
         Ok(img) => img,
         Err(e) => return Err(ImageError(format!("image error: {:?}  file: {:?}", e, texture_path))),
     };
 
+    Ok(create_material(context, img, texture_path.into(), texture_config))
+}
+
+fn create_material(context: &mut GpuContext, mut img: DynamicImage, texture_path: OsString, texture_config: &TextureConfig) -> Material {
     let (width, height) = img.dimensions();
 
     if texture_config.flip_v {
@@ -116,8 +131,8 @@ pub fn load_texture(context: &mut GpuContext, texture_path: &PathBuf, texture_co
 
     let bind_group = create_texture_bind_group(context, &bind_group_layout, &texture_view, &texture_sampler);
 
-    let texture = Material {
-        texture_path: texture_path.into(),
+    Material {
+        texture_path,
         texture_type: texture_config.texture_type,
         texture: wgpu_texture.into(),
         view: texture_view.into(),
@@ -125,9 +140,7 @@ pub fn load_texture(context: &mut GpuContext, texture_path: &PathBuf, texture_co
         bind_group: bind_group.into(),
         width,
         height,
-    };
-
-    Ok(texture)
+    }
 }
 
 pub fn create_material_bind_group_layout(context: &GpuContext) -> BindGroupLayout {
@@ -1,9 +1,13 @@
-use crate::animator::{AnimationClip, Animator, WeightedAnimation};
+use crate::animator::{AnimationClip, AnimationRepeat, Animator, BoneMask, FiredAnimationEvent, WeightedAnimation};
 use crate::gpu_context::GpuContext;
+use crate::ik::IkConstraint;
 use crate::model_mesh::ModelMesh;
+use crate::skinning::{ComputeSkinning, SkinningMethod};
 use crate::texture_config::TextureType;
+use crate::transform::Transform;
 use glam::Mat4;
 use std::cell::RefCell;
+use std::mem;
 use std::rc::Rc;
 use std::time::Duration;
 use wgpu::{BindGroup, Buffer};
@@ -16,9 +20,12 @@ pub struct Model {
     pub animator: RefCell<Animator>,
     pub model_transform: Mat4,
     pub model_transform_buffer: Buffer,
-    pub node_transform_buffer: Buffer,
+    pub final_nodes_matrices_buffer: Buffer,
     pub final_bones_matrices_buffer: Buffer,
+    pub final_bones_dual_quats_buffer: Buffer,
     pub bind_group: BindGroup, // binds buffers into a group
+    /// Set when the model was built with `ModelBuilder::use_compute_skinning` on a device that supports it.
+    pub compute_skinning: Option<ComputeSkinning>,
 }
 
 pub struct ModelUniform {
@@ -32,6 +39,22 @@ impl Model {
         self.animator.borrow_mut().update_animation(delta_time);
     }
 
+    pub fn drain_animation_events(&self) -> Vec<FiredAnimationEvent> {
+        self.animator.borrow().drain_events()
+    }
+
+    pub fn drain_completed_clips(&self) -> Vec<Rc<AnimationClip>> {
+        self.animator.borrow().drain_completed_clips()
+    }
+
+    pub fn take_root_motion(&self) -> Transform {
+        self.animator.borrow().take_root_motion()
+    }
+
+    pub fn get_clip(&self, animation_name: &str, repeat: AnimationRepeat) -> Option<Rc<AnimationClip>> {
+        self.animator.borrow().get_clip(animation_name, repeat)
+    }
+
     pub fn play_clip(&self, clip: &Rc<AnimationClip>) {
         self.animator.borrow_mut().play_clip(clip);
     }
@@ -40,12 +63,37 @@ impl Model {
         self.animator.borrow_mut().play_clip_with_transition(clip, transition_duration);
     }
 
+    pub fn set_layer(&self, name: &str, clip: &Rc<AnimationClip>, weight: f32, mask: BoneMask) {
+        self.animator.borrow_mut().set_layer(name, clip, weight, mask);
+    }
+
+    pub fn set_additive_layer(&self, name: &str, clip: &Rc<AnimationClip>, weight: f32, mask: BoneMask) {
+        self.animator.borrow_mut().set_additive_layer(name, clip, weight, mask);
+    }
+
+    pub fn set_layer_weight(&self, name: &str, weight: f32) {
+        self.animator.borrow_mut().set_layer_weight(name, weight);
+    }
+
+    pub fn set_ik_constraint(&self, name: &str, constraint: IkConstraint) {
+        self.animator.borrow_mut().set_ik_constraint(name, constraint);
+    }
+
+    pub fn set_morph_weights(&self, node_name: &str, weights: &[f32]) {
+        self.animator.borrow_mut().set_morph_weights(node_name, weights);
+    }
+
+    pub fn clear_morph_weights(&self, node_name: &str) {
+        self.animator.borrow_mut().clear_morph_weights(node_name);
+    }
+
     pub fn play_weight_animations(&mut self, weighted_animation: &[WeightedAnimation], frame_time: f32) {
         self.animator.borrow_mut().play_weight_animations(weighted_animation, frame_time);
     }
 
     pub fn update_model_buffers(&self, context: &GpuContext, model_transform: &Mat4) {
         let animator = self.animator.borrow();
+        let final_nodes = animator.final_node_matrices.borrow();
         let final_bones = animator.final_bone_matrices.borrow();
 
         context.queue.write_buffer(
@@ -54,20 +102,54 @@ impl Model {
             bytemuck::cast_slice(&model_transform.to_cols_array()),
         );
 
-        context
-            .queue
-            .write_buffer(&self.final_bones_matrices_buffer, 0, bytemuck::cast_slice(final_bones.as_ref()));
+        write_palette(context, &self.final_nodes_matrices_buffer, &final_nodes);
+        write_palette(context, &self.final_bones_matrices_buffer, &final_bones);
+
+        if animator.skinning_method == SkinningMethod::DualQuaternion {
+            write_palette(
+                context,
+                &self.final_bones_dual_quats_buffer,
+                &animator.final_bone_dual_quats.borrow(),
+            );
+        }
     }
 
     pub fn update_mesh_buffers(&self, context: &GpuContext, mesh: &ModelMesh) {
         let animator = self.animator.borrow();
-        let final_nodes = animator.final_node_matrices.borrow();
 
-        let node_transform = &final_nodes[mesh.id as usize].to_cols_array();
+        if mesh.morph_target_count > 0 {
+            let mut morph_weights = animator
+                .mesh_morph_weights(mesh.id as u32)
+                .unwrap_or_else(|| mesh.default_morph_weights.clone());
+            morph_weights.resize(mesh.morph_target_count, 0.0);
 
-        context
-            .queue
-            .write_buffer(&self.node_transform_buffer, 0, bytemuck::cast_slice(node_transform));
+            context
+                .queue
+                .write_buffer(&mesh.morph_weights_buffer, 0, bytemuck::cast_slice(&morph_weights));
+        }
+    }
+
+    /// Records the compute pass that skins every mesh into its skinned vertex buffer, once per frame after
+    /// `update_model_buffers` and before the passes that draw the model. Does nothing without compute skinning.
+    pub fn skin_meshes(&self, context: &GpuContext, encoder: &mut wgpu::CommandEncoder) {
+        let Some(compute_skinning) = &self.compute_skinning else {
+            return;
+        };
+
+        for mesh in self.meshes.iter() {
+            self.update_mesh_buffers(context, mesh);
+        }
+
+        compute_skinning.dispatch(encoder);
+    }
+
+    /// The mesh's vertices skinned by `skin_meshes`, in the layout of `SkinnedVertex`. None when the model
+    /// skins in the vertex shader, then the mesh's own vertex buffer is drawn.
+    pub fn skinned_vertex_buffer(&self, mesh: &ModelMesh) -> Option<&Buffer> {
+        self.compute_skinning
+            .as_ref()
+            .and_then(|compute_skinning| compute_skinning.get_skinned_mesh(mesh.id))
+            .map(|skinned_mesh| &skinned_mesh.vertex_buffer)
     }
 
     pub fn get_material_bind_group<'a>(&'a self, mesh: &'a ModelMesh, texture_type: TextureType) -> &BindGroup {
@@ -76,3 +158,9 @@ impl Model {
         &diffuse_material.bind_group
     }
 }
+
+/// Writes as much of the palette as fits the buffer, which is smaller than the palette for the uniform fallback.
+fn write_palette<T: bytemuck::Pod>(context: &GpuContext, buffer: &Buffer, palette: &[T]) {
+    let count = palette.len().min(buffer.size() as usize / mem::size_of::<T>());
+    context.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&palette[..count]));
+}
@@ -1,8 +1,11 @@
 use crate::node_animation::NodeAnimation;
 use crate::transform::Transform;
 use glam::Mat4;
+#[cfg(feature = "russimp")]
 use log::debug;
+#[cfg(feature = "russimp")]
 use russimp::animation::Animation;
+#[cfg(feature = "russimp")]
 use russimp::scene::Scene;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -36,6 +39,7 @@ impl BoneData {
 
 #[derive(Debug, Clone)]
 pub struct ModelAnimation {
+    pub name: Rc<str>,
     pub duration: f32,
     pub ticks_per_second: f32,
     pub node_animations: RefCell<Vec<NodeAnimation>>,
@@ -44,6 +48,7 @@ pub struct ModelAnimation {
 impl Default for ModelAnimation {
     fn default() -> Self {
         ModelAnimation {
+            name: Rc::from(""),
             duration: 0.0,
             ticks_per_second: 0.0,
             node_animations: RefCell::new(vec![]),
@@ -52,27 +57,59 @@ impl Default for ModelAnimation {
 }
 
 impl ModelAnimation {
-    pub fn new(scene: &Scene) -> Self {
-        if scene.animations.is_empty() {
-            return ModelAnimation::default();
+    /// An additive copy of the animation, with every key stored as its difference from the pose at `reference_tick`.
+    pub fn to_additive(&self, name: &str, reference_tick: f32) -> ModelAnimation {
+        let node_animations = self
+            .node_animations
+            .borrow()
+            .iter()
+            .map(|node_animation| node_animation.to_additive(&node_animation.get_animation_transform(reference_tick)))
+            .collect();
+
+        ModelAnimation {
+            name: Rc::from(name),
+            duration: self.duration,
+            ticks_per_second: self.ticks_per_second,
+            node_animations: RefCell::new(node_animations),
         }
+    }
+}
 
-        let duration = scene.animations[0].duration as f32;
-        let ticks_per_second = scene.animations[0].ticks_per_second as f32;
+#[cfg(feature = "russimp")]
+impl ModelAnimation {
+    /// Reads every animation in the scene. Unnamed animations are named by their index.
+    pub fn from_scene(scene: &Scene) -> Vec<ModelAnimation> {
+        scene
+            .animations
+            .iter()
+            .enumerate()
+            .map(|(index, animation)| {
+                let mut model_animation = ModelAnimation::new(animation);
+                if model_animation.name.is_empty() {
+                    model_animation.name = Rc::from(format!("animation_{}", index));
+                }
+                model_animation
+            })
+            .collect()
+    }
 
-        debug!("animation - duration: {}   ticks_per_second: {}", &duration, &ticks_per_second);
+    pub fn new(animation: &Animation) -> Self {
+        let duration = animation.duration as f32;
+        let ticks_per_second = animation.ticks_per_second as f32;
 
-        // debug!("root_node: {:#?}", &root_node);
-        // debug!("bone_data_map: {:#?}", &model.bone_data_map.borrow());
+        debug!(
+            "animation: {}  duration: {}   ticks_per_second: {}",
+            animation.name, duration, ticks_per_second
+        );
 
         let mut model_animation = ModelAnimation {
-            // model: model.clone(),
+            name: Rc::from(animation.name.as_str()),
             duration,
             ticks_per_second,
             node_animations: vec![].into(),
         };
 
-        model_animation.read_channel_node_animations(&scene.animations[0]);
+        model_animation.read_channel_node_animations(animation);
         model_animation
     }
 
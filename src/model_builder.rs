@@ -1,20 +1,32 @@
-use crate::animator::{Animator, MAX_BONES};
+#[cfg(feature = "russimp")]
+use crate::animator::Animator;
+use crate::animator::{MAX_BONES, MAX_NODES};
 use crate::error::Error;
-use crate::error::Error::{MeshError, SceneError};
+use crate::error::Error::MeshError;
+#[cfg(feature = "russimp")]
+use crate::error::Error::SceneError;
 use crate::gpu_context::GpuContext;
 use crate::hash_map::HashMap;
 use crate::material::Material;
 use crate::model::Model;
 use crate::model_animation::{BoneData, BoneName};
-use crate::model_mesh::{ModelMesh, ModelVertex};
+use crate::model_mesh::ModelMesh;
+#[cfg(feature = "russimp")]
+use crate::model_mesh::{ModelVertex, MorphTargets};
+use crate::pose_cache::PoseCache;
+use crate::skinning::{ComputeSkinning, SkinningMethod};
 use crate::texture_config::{TextureConfig, TextureFilter, TextureType, TextureWrap};
+#[cfg(feature = "russimp")]
 use crate::transform::Transform;
 use crate::utils::get_exists_filename;
 use glam::*;
-use log::debug;
+use log::{debug, warn};
+#[cfg(feature = "russimp")]
 use russimp::node::Node;
+#[cfg(feature = "russimp")]
 use russimp::scene::{PostProcess, Scene};
 use std::cell::RefCell;
+use std::ffi::OsStr;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -23,6 +35,47 @@ use wgpu::{BindGroup, BindGroupLayout, Buffer};
 
 pub const MODEL_BIND_GROUP_LAYOUT: &str = "model_bind_group_layout";
 
+/// The WGSL declarations of the model bind group at group 1, the model transform and the node, bone and
+/// bone dual quaternion palettes. The palettes are storage buffers, or on devices without storage buffers in
+/// vertex shaders, uniform arrays of `MAX_NODES` and `MAX_BONES` entries. `node_count()`, `bone_count()` and
+/// `dual_quat_count()` give their sizes.
+pub fn model_palettes_wgsl(storage_palettes: bool) -> String {
+    let palettes = if storage_palettes {
+        String::from(
+            "@group(1) @binding(1) var<storage, read> node_transforms: array<mat4x4<f32>>;
+@group(1) @binding(2) var<storage, read> bone_transforms: array<mat4x4<f32>>;
+@group(1) @binding(3) var<storage, read> bone_dual_quats: array<DualQuat>;
+
+fn node_count() -> u32 { return arrayLength(&node_transforms); }
+fn bone_count() -> u32 { return arrayLength(&bone_transforms); }
+fn dual_quat_count() -> u32 { return arrayLength(&bone_dual_quats); }
+",
+        )
+    } else {
+        format!(
+            "@group(1) @binding(1) var<uniform> node_transforms: array<mat4x4<f32>, {MAX_NODES}>;
+@group(1) @binding(2) var<uniform> bone_transforms: array<mat4x4<f32>, {MAX_BONES}>;
+@group(1) @binding(3) var<uniform> bone_dual_quats: array<DualQuat, {MAX_BONES}>;
+
+fn node_count() -> u32 {{ return {MAX_NODES}u; }}
+fn bone_count() -> u32 {{ return {MAX_BONES}u; }}
+fn dual_quat_count() -> u32 {{ return {MAX_BONES}u; }}
+"
+        )
+    };
+
+    format!(
+        "struct DualQuat {{
+    real: vec4<f32>,
+    dual: vec4<f32>,
+    scale: vec4<f32>,
+}}
+
+@group(1) @binding(0) var<uniform> model_transform: mat4x4<f32>;
+{palettes}"
+    )
+}
+
 #[derive(Debug)]
 pub struct AddedTextures {
     mesh_name: String,
@@ -42,6 +95,10 @@ pub struct ModelBuilder {
     pub flip_v: bool,
     pub flip_h: bool,
     pub load_textures: bool,
+    pub use_gltf: bool,
+    pub compute_skinning: bool,
+    pub skinning_method: SkinningMethod,
+    pub pose_cache: Option<Rc<PoseCache>>,
     pub textures_cache: RefCell<Vec<Rc<Material>>>,
     added_textures: Vec<AddedTextures>,
     pub mesh_count: i32,
@@ -63,6 +120,10 @@ impl ModelBuilder {
             flip_v: false,
             flip_h: false,
             load_textures: true,
+            use_gltf: !cfg!(feature = "russimp"),
+            compute_skinning: false,
+            skinning_method: SkinningMethod::Linear,
+            pose_cache: None,
             added_textures: vec![],
             mesh_count: 0,
         }
@@ -83,6 +144,33 @@ impl ModelBuilder {
         self
     }
 
+    /// Loads the model with the native glTF loader instead of russimp.
+    /// Always the case when the russimp feature is disabled.
+    pub fn use_gltf_loader(mut self) -> Self {
+        self.use_gltf = true;
+        self
+    }
+
+    /// Skins the meshes in a compute pass, see `Model::skin_meshes`. Falls back to skinning in the
+    /// vertex shader when the device can't run the pass.
+    pub fn use_compute_skinning(mut self) -> Self {
+        self.compute_skinning = true;
+        self
+    }
+
+    /// Fills the dual quaternion bone palette each update and skins with it in the compute pass. Vertex
+    /// shaders drawing the model call `skin_model_vertex` from `skinning_wgsl` with the model's method.
+    pub fn use_dual_quaternion_skinning(mut self) -> Self {
+        self.skinning_method = SkinningMethod::DualQuaternion;
+        self
+    }
+
+    /// Shares the sampled poses with the other models built with the cache, which must have the same skeleton.
+    pub fn with_pose_cache(mut self, pose_cache: &Rc<PoseCache>) -> Self {
+        self.pose_cache = Some(pose_cache.clone());
+        self
+    }
+
     pub fn add_texture(mut self, mesh_name: impl Into<String>, texture_type: TextureType, texture_filename: impl Into<String>) -> Self {
         let added_texture = AddedTextures {
             mesh_name: mesh_name.into(),
@@ -94,16 +182,25 @@ impl ModelBuilder {
     }
 
     pub fn build(mut self, context: &mut GpuContext) -> Result<Model, Error> {
-        let scene = ModelBuilder::load_russimp_scene(self.filepath.as_str())?;
+        #[cfg(feature = "russimp")]
+        let mut animator = if self.use_gltf {
+            self.load_gltf_model(context)?
+        } else {
+            self.load_russimp_model(context)?
+        };
 
-        self.load_model(context, &scene)?;
+        #[cfg(not(feature = "russimp"))]
+        let mut animator = self.load_gltf_model(context)?;
+
+        animator.skinning_method = self.skinning_method;
+        animator.pose_cache = self.pose_cache.clone();
 
         self.add_textures(context)?;
 
-        let animator = Animator::new(&scene, self.bone_data_map);
+        let storage_palettes = context.supports_vertex_storage();
 
         if !context.bind_layout_cache.contains_key(MODEL_BIND_GROUP_LAYOUT) {
-            let layout = Self::create_model_bind_group_layout(context);
+            let layout = Self::create_model_bind_group_layout(context, storage_palettes);
             context
                 .bind_layout_cache
                 .insert(String::from(MODEL_BIND_GROUP_LAYOUT), layout.into());
@@ -112,31 +209,83 @@ impl ModelBuilder {
         let bind_group_layout = context.bind_layout_cache.get(MODEL_BIND_GROUP_LAYOUT).unwrap();
 
         let model_transform_buffer = Self::create_transform_buffer(context, "model transform buffer", &Mat4::IDENTITY);
-        let node_transform_buffer = Self::create_transform_buffer(context, "node transform buffer", &Mat4::IDENTITY);
-        let final_bones_matrices_buffer = Self::create_final_bones_buffer(context, &animator.final_bone_matrices);
+        let final_nodes_matrices_buffer = Self::create_palette_buffer(
+            context,
+            "final nodes matrices",
+            &animator.final_node_matrices,
+            MAX_NODES,
+            storage_palettes,
+        );
+        let final_bones_matrices_buffer = Self::create_palette_buffer(
+            context,
+            "final bones matrices",
+            &animator.final_bone_matrices,
+            MAX_BONES,
+            storage_palettes,
+        );
+
+        let final_bones_dual_quats_buffer = Self::create_palette_buffer(
+            context,
+            "final bones dual quats",
+            &animator.final_bone_dual_quats,
+            MAX_BONES,
+            storage_palettes,
+        );
 
         let bind_group = Self::create_model_bind_group(
             context,
             &bind_group_layout,
             &model_transform_buffer,
-            &node_transform_buffer,
+            &final_nodes_matrices_buffer,
             &final_bones_matrices_buffer,
+            &final_bones_dual_quats_buffer,
         );
 
+        let compute_skinning = if self.compute_skinning && context.supports_compute_skinning() {
+            Some(ComputeSkinning::new(
+                context,
+                &self.meshes,
+                &final_nodes_matrices_buffer,
+                &final_bones_matrices_buffer,
+                &final_bones_dual_quats_buffer,
+                self.skinning_method,
+            ))
+        } else {
+            if self.compute_skinning {
+                warn!(
+                    "{}: the device can't run the compute skinning pass, skinning in the vertex shader",
+                    self.name
+                );
+            }
+            None
+        };
+
         let model = Model {
             name: Rc::from(self.name),
             meshes: Rc::from(self.meshes),
             animator: animator.into(),
             model_transform: Mat4::IDENTITY,
             model_transform_buffer,
-            node_transform_buffer,
+            final_nodes_matrices_buffer,
             final_bones_matrices_buffer,
+            final_bones_dual_quats_buffer,
             bind_group,
+            compute_skinning,
         };
 
         Ok(model)
     }
 
+    #[cfg(feature = "russimp")]
+    fn load_russimp_model(&mut self, context: &mut GpuContext) -> Result<Animator, Error> {
+        let scene = ModelBuilder::load_russimp_scene(self.filepath.as_str())?;
+
+        self.load_model(context, &scene)?;
+
+        Ok(Animator::new(&scene, RefCell::new(self.bone_data_map.take())))
+    }
+
+    #[cfg(feature = "russimp")]
     pub fn load_russimp_scene(file_path: &str) -> Result<Scene, Error> {
         let scene = Scene::from_file(
             file_path,
@@ -154,6 +303,7 @@ impl ModelBuilder {
         Ok(scene)
     }
 
+    #[cfg(feature = "russimp")]
     fn load_model(&mut self, context: &mut GpuContext, scene: &Scene) -> Result<(), Error> {
         match &scene.root {
             None => Err(SceneError("Error getting scene root node".to_string())),
@@ -161,6 +311,7 @@ impl ModelBuilder {
         }
     }
 
+    #[cfg(feature = "russimp")]
     #[allow(clippy::needless_range_loop)]
     fn process_node(&mut self, context: &mut GpuContext, node: &Rc<Node>, scene: &Scene) -> Result<(), Error> {
         for mesh_id in &node.meshes {
@@ -176,6 +327,7 @@ impl ModelBuilder {
         Ok(())
     }
 
+    #[cfg(feature = "russimp")]
     #[allow(clippy::needless_range_loop)]
     fn process_mesh(&mut self, context: &mut GpuContext, r_mesh: &russimp::mesh::Mesh, scene: &Scene) -> Result<ModelMesh, Error> {
         let mut vertices: Vec<ModelVertex> = vec![];
@@ -223,12 +375,21 @@ impl ModelBuilder {
 
         self.extract_bone_weights_for_vertices(&mut vertices, r_mesh);
 
-        let mesh = ModelMesh::new(context, self.mesh_count, &r_mesh.name, vertices, indices, materials);
+        let mesh = ModelMesh::new(
+            context,
+            self.mesh_count,
+            &r_mesh.name,
+            vertices,
+            indices,
+            materials,
+            MorphTargets::default(),
+        );
 
         self.mesh_count += 1;
         Ok(mesh)
     }
 
+    #[cfg(feature = "russimp")]
     fn extract_bone_weights_for_vertices(&mut self, vertices: &mut [ModelVertex], r_mesh: &russimp::mesh::Mesh) {
         let mut bone_data_map = self.bone_data_map.borrow_mut();
 
@@ -261,6 +422,10 @@ impl ModelBuilder {
                 vertices[vertex_id].set_bone_data(bone_id, weight);
             }
         }
+
+        for vertex in vertices.iter_mut() {
+            vertex.normalize_bone_weights();
+        }
     }
 
     fn add_textures(&mut self, context: &mut GpuContext) -> Result<(), Error> {
@@ -280,7 +445,7 @@ impl ModelBuilder {
         Ok(())
     }
 
-    fn load_or_get_material(
+    pub(crate) fn load_or_get_material(
         &self,
         context: &mut GpuContext,
         texture_type: &TextureType,
@@ -288,36 +453,37 @@ impl ModelBuilder {
     ) -> Result<Rc<Material>, Error> {
         let filepath = get_exists_filename(&self.directory, texture_filename)?;
 
-        let mut texture_cache = self.textures_cache.borrow_mut();
-
-        let cached_texture = texture_cache.iter().find(|t| t.texture_path == filepath.clone().into_os_string());
-
-        match cached_texture {
-            None => {
-                let texture = Rc::new(Material::new(
-                    context,
-                    &filepath,
-                    &TextureConfig {
-                        flip_v: self.flip_v,
-                        flip_h: self.flip_h,
-                        gamma_correction: self.gamma_correction,
-                        filter: TextureFilter::Linear,
-                        wrap: TextureWrap::Repeat,
-                        texture_type: *texture_type,
-                    },
-                )?);
-                debug!("loaded texture: {:?}", &texture);
-                texture_cache.push(texture.clone());
-                Ok(texture)
-            }
-            Some(texture) => {
-                let mut texture_new = texture.deref().clone();
-                if texture_new.texture_type != *texture_type {
-                    texture_new.texture_type = *texture_type;
-                }
-                debug!("cloned texture: {:?}", &texture);
-                Ok(Rc::new(texture_new))
-            }
+        if let Some(texture) = self.get_cached_material(filepath.as_os_str(), texture_type) {
+            return Ok(texture);
+        }
+
+        let texture = Rc::new(Material::new(context, &filepath, &self.texture_config(texture_type))?);
+        debug!("loaded texture: {:?}", &texture);
+        self.textures_cache.borrow_mut().push(texture.clone());
+        Ok(texture)
+    }
+
+    pub(crate) fn get_cached_material(&self, texture_path: &OsStr, texture_type: &TextureType) -> Option<Rc<Material>> {
+        let texture_cache = self.textures_cache.borrow();
+
+        let texture = texture_cache.iter().find(|t| t.texture_path == texture_path)?;
+
+        let mut texture_new = texture.deref().clone();
+        if texture_new.texture_type != *texture_type {
+            texture_new.texture_type = *texture_type;
+        }
+        debug!("cloned texture: {:?}", &texture);
+        Some(Rc::new(texture_new))
+    }
+
+    pub(crate) fn texture_config(&self, texture_type: &TextureType) -> TextureConfig {
+        TextureConfig {
+            flip_v: self.flip_v,
+            flip_h: self.flip_h,
+            gamma_correction: self.gamma_correction,
+            filter: TextureFilter::Linear,
+            wrap: TextureWrap::Repeat,
+            texture_type: *texture_type,
         }
     }
 
@@ -329,15 +495,53 @@ impl ModelBuilder {
         })
     }
 
-    fn create_final_bones_buffer(context: &GpuContext, data: &RefCell<Box<[Mat4]>>) -> Buffer {
+    /// A storage buffer sized to the palette, or a uniform buffer of `uniform_count` matrices that
+    /// holds as much of the palette as fits.
+    fn create_palette_buffer<T: bytemuck::Pod + Default>(
+        context: &GpuContext,
+        label: &str,
+        palette: &RefCell<Box<[T]>>,
+        uniform_count: usize,
+        storage_palettes: bool,
+    ) -> Buffer {
+        let palette = palette.borrow();
+
+        if storage_palettes {
+            return context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(palette.as_ref()),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            });
+        }
+
+        if palette.len() > uniform_count {
+            warn!(
+                "{}: {} matrices don't fit the uniform fallback of {}, the rest are dropped",
+                label,
+                palette.len(),
+                uniform_count
+            );
+        }
+
+        let mut contents = vec![T::default(); uniform_count];
+        let count = palette.len().min(uniform_count);
+        contents[..count].copy_from_slice(&palette[..count]);
+
         context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("final bones matrices"),
-            contents: bytemuck::cast_slice(data.borrow().as_ref()),
+            label: Some(label),
+            contents: bytemuck::cast_slice(&contents),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         })
     }
 
-    fn create_model_bind_group_layout(context: &GpuContext) -> BindGroupLayout {
+    /// The layout of the model bind group declared by `model_palettes_wgsl`.
+    pub fn create_model_bind_group_layout(context: &GpuContext, storage_palettes: bool) -> BindGroupLayout {
+        let palette_type = if storage_palettes {
+            wgpu::BufferBindingType::Storage { read_only: true }
+        } else {
+            wgpu::BufferBindingType::Uniform
+        };
+
         context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 // 0: model transform
@@ -351,12 +555,12 @@ impl ModelBuilder {
                     },
                     count: None,
                 },
-                // 1: node_transform
+                // 1: final_nodes_matrices
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::VERTEX,
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
+                        ty: palette_type,
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
@@ -367,9 +571,20 @@ impl ModelBuilder {
                     binding: 2,
                     visibility: wgpu::ShaderStages::VERTEX,
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
+                        ty: palette_type,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // 3: final_bones_dual_quats
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: palette_type,
                         has_dynamic_offset: false,
-                        min_binding_size: wgpu::BufferSize::new((MAX_BONES * 16) as _),
+                        min_binding_size: None,
                     },
                     count: None,
                 },
@@ -382,8 +597,9 @@ impl ModelBuilder {
         context: &GpuContext,
         bind_group_layout: &BindGroupLayout,
         model_transform: &Buffer,
-        node_transform: &Buffer,
+        final_nodes: &Buffer,
         final_bones: &Buffer,
+        final_bones_dual_quats: &Buffer,
     ) -> BindGroup {
         context.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: bind_group_layout,
@@ -394,12 +610,16 @@ impl ModelBuilder {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: node_transform.as_entire_binding(),
+                    resource: final_nodes.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
                     resource: final_bones.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: final_bones_dual_quats.as_entire_binding(),
+                },
             ],
             label: Some("model bind group"),
         })
@@ -4,6 +4,9 @@ use glam::*;
 use std::mem;
 use std::rc::Rc;
 use wgpu::util::DeviceExt;
+use wgpu::{BindGroup, BindGroupLayout};
+
+pub const MESH_BIND_GROUP_LAYOUT: &str = "mesh_bind_group_layout";
 
 const MAX_BONE_INFLUENCE: usize = 4;
 const OFFSET_OF_NORMAL: usize = mem::offset_of!(ModelVertex, normal);
@@ -56,6 +59,26 @@ impl ModelVertex {
         }
     }
 
+    /// Scales the weights of the vertex's bones to sum to 1, exporters don't always do it.
+    pub fn normalize_bone_weights(&mut self) {
+        let mut total = 0.0;
+        for i in 0..MAX_BONE_INFLUENCE {
+            if self.bone_ids[i] >= 0 {
+                total += self.bone_weights[i];
+            }
+        }
+
+        if total <= 0.0 {
+            return;
+        }
+
+        for i in 0..MAX_BONE_INFLUENCE {
+            if self.bone_ids[i] >= 0 {
+                self.bone_weights[i] /= total;
+            }
+        }
+    }
+
     pub fn vertex_description() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<ModelVertex>() as wgpu::BufferAddress,
@@ -114,6 +137,25 @@ impl Default for ModelVertex {
     }
 }
 
+/// The change a morph target makes to one vertex at full weight. Each is a vec4
+/// to match the alignment of vec3 in a WGSL storage array, w is unused.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MorphTargetDelta {
+    pub position: Vec4,
+    pub normal: Vec4,
+    pub tangent: Vec4,
+}
+
+/// The morph targets of a mesh, the deltas of the first target for every vertex, then the second target's and so on.
+#[derive(Debug, Clone, Default)]
+pub struct MorphTargets {
+    pub target_count: usize,
+    pub deltas: Vec<MorphTargetDelta>,
+    /// The weights used when neither an animation nor code sets them.
+    pub default_weights: Vec<f32>,
+}
+
 #[derive(Debug)]
 pub struct ModelMesh {
     pub id: i32,
@@ -125,21 +167,39 @@ pub struct ModelMesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    pub num_vertices: u32,
+    pub morph_target_count: usize,
+    pub default_morph_weights: Vec<f32>,
+    pub morph_deltas_buffer: wgpu::Buffer,
+    pub morph_weights_buffer: wgpu::Buffer,
+    /// The mesh's index in the node palette, the mesh id.
+    pub mesh_index_buffer: wgpu::Buffer,
+    /// The morph deltas and weights and the mesh index. Meshes without morph targets have the
+    /// group too, so one pipeline draws meshes with and without them.
+    pub mesh_bind_group: BindGroup,
 }
 
 impl ModelMesh {
     pub fn new(
-        context: &GpuContext,
+        context: &mut GpuContext,
         id: i32,
         name: impl Into<String>,
         vertices: Vec<ModelVertex>,
         indices: Vec<u32>,
         materials: Vec<Rc<Material>>,
+        morph_targets: MorphTargets,
     ) -> ModelMesh {
+        // the compute skinning pass reads the vertices as storage
+        let vertex_usage = if context.supports_compute_skinning() {
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::VERTEX
+        };
+
         let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: vertex_usage,
         });
 
         let index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -149,6 +209,73 @@ impl ModelMesh {
         });
 
         let num_elements = indices.len() as u32;
+        let num_vertices = vertices.len() as u32;
+
+        // without storage buffers in vertex shaders the morph targets aren't bound, see `skinning_wgsl`
+        let storage_morph_targets = context.supports_vertex_storage();
+        let morph_usage = if storage_morph_targets {
+            wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::COPY_DST
+        };
+
+        // storage buffers can't be empty, a mesh without targets gets one target with a zero weight
+        let (deltas, weights) = if morph_targets.target_count == 0 {
+            (vec![MorphTargetDelta::default()], vec![0.0f32])
+        } else {
+            let mut weights = morph_targets.default_weights.clone();
+            weights.resize(morph_targets.target_count, 0.0);
+            (morph_targets.deltas, weights)
+        };
+
+        let morph_deltas_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Deltas Buffer"),
+            contents: bytemuck::cast_slice(&deltas),
+            usage: morph_usage,
+        });
+
+        let morph_weights_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Weights Buffer"),
+            contents: bytemuck::cast_slice(&weights),
+            usage: morph_usage | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // padded to 16 bytes for the uniform
+        let mesh_index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(&[id as u32, 0, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        if !context.bind_layout_cache.contains_key(MESH_BIND_GROUP_LAYOUT) {
+            let layout = create_mesh_bind_group_layout(context, storage_morph_targets);
+            context
+                .bind_layout_cache
+                .insert(String::from(MESH_BIND_GROUP_LAYOUT), layout.into());
+        }
+
+        let bind_group_layout = context.bind_layout_cache.get(MESH_BIND_GROUP_LAYOUT).unwrap();
+
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 2,
+            resource: mesh_index_buffer.as_entire_binding(),
+        }];
+        if storage_morph_targets {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 0,
+                resource: morph_deltas_buffer.as_entire_binding(),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1,
+                resource: morph_weights_buffer.as_entire_binding(),
+            });
+        }
+
+        let mesh_bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: bind_group_layout,
+            entries: &entries,
+            label: Some("mesh bind group"),
+        });
 
         ModelMesh {
             id,
@@ -159,6 +286,65 @@ impl ModelMesh {
             vertex_buffer,
             index_buffer,
             num_elements,
+            num_vertices,
+            morph_target_count: morph_targets.target_count,
+            default_morph_weights: morph_targets.default_weights,
+            morph_deltas_buffer,
+            morph_weights_buffer,
+            mesh_index_buffer,
+            mesh_bind_group,
         }
     }
 }
+
+/// The layout of the mesh bind group, the morph deltas and weights as storage buffers and the mesh index as a uniform.
+/// Without `storage_morph_targets` it only has the mesh index, matching `skinning_wgsl(false)`.
+pub fn create_mesh_bind_group_layout(context: &GpuContext, storage_morph_targets: bool) -> BindGroupLayout {
+    let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    // 2: mesh index
+    let mut entries = vec![buffer_entry(2, wgpu::BufferBindingType::Uniform)];
+    if storage_morph_targets {
+        // 0: morph deltas
+        entries.push(buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: true }));
+        // 1: morph weights
+        entries.push(buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }));
+    }
+
+    context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("mesh bind group layout"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_mesh::ModelVertex;
+
+    #[test]
+    fn test_normalize_bone_weights() {
+        let mut vertex = ModelVertex::new();
+        vertex.set_bone_data(3, 0.5);
+        vertex.set_bone_data(7, 1.5);
+        vertex.normalize_bone_weights();
+
+        let weights = vertex.bone_weights;
+        assert_eq!(weights, [0.25, 0.75, 0.0, 0.0]);
+
+        // a vertex without bones keeps its zero weights
+        let mut vertex = ModelVertex::new();
+        vertex.normalize_bone_weights();
+
+        let weights = vertex.bone_weights;
+        assert_eq!(weights, [0.0; 4]);
+    }
+}
@@ -1,25 +1,135 @@
 use crate::transform::Transform;
-use glam::{Quat, Vec3};
+use glam::{Quat, Vec3, Vec4};
+#[cfg(feature = "russimp")]
 use log::debug;
-use russimp::animation::{NodeAnim, QuatKey, VectorKey};
+#[cfg(feature = "russimp")]
+use russimp::animation::{AnimBehaviour, NodeAnim, QuatKey, VectorKey};
+use std::ops::{Add, Mul};
 use std::rc::Rc;
 
+/// How the values between two keys of a channel are found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyInterpolation {
+    #[default]
+    Linear,
+    /// Holds each key's value until the next key.
+    Step,
+    /// A cubic Hermite spline through the keys, shaped by their tangents.
+    CubicSpline,
+}
+
+/// What a channel does for times before its first key or after its last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimationBehaviour {
+    /// Holds the value of the nearest key.
+    #[default]
+    Constant,
+    /// Repeats the keys from the first to the last.
+    Repeat,
+}
+
+/// Tangents are only used by cubic spline channels and are the change in value per tick.
 #[derive(Debug, Clone)]
 pub struct KeyPosition {
     pub position: Vec3,
     pub time_stamp: f32,
+    pub in_tangent: Vec3,
+    pub out_tangent: Vec3,
 }
 
+impl KeyPosition {
+    pub fn new(position: Vec3, time_stamp: f32) -> Self {
+        KeyPosition {
+            position,
+            time_stamp,
+            in_tangent: Vec3::ZERO,
+            out_tangent: Vec3::ZERO,
+        }
+    }
+
+    pub fn with_tangents(mut self, in_tangent: Vec3, out_tangent: Vec3) -> Self {
+        self.in_tangent = in_tangent;
+        self.out_tangent = out_tangent;
+        self
+    }
+}
+
+/// The tangents are quaternion components, not rotations.
 #[derive(Debug, Clone)]
 pub struct KeyRotation {
     pub orientation: Quat,
     pub time_stamp: f32,
+    pub in_tangent: Vec4,
+    pub out_tangent: Vec4,
+}
+
+impl KeyRotation {
+    pub fn new(orientation: Quat, time_stamp: f32) -> Self {
+        KeyRotation {
+            orientation,
+            time_stamp,
+            in_tangent: Vec4::ZERO,
+            out_tangent: Vec4::ZERO,
+        }
+    }
+
+    pub fn with_tangents(mut self, in_tangent: Vec4, out_tangent: Vec4) -> Self {
+        self.in_tangent = in_tangent;
+        self.out_tangent = out_tangent;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct KeyScale {
     pub scale: Vec3,
     pub time_stamp: f32,
+    pub in_tangent: Vec3,
+    pub out_tangent: Vec3,
+}
+
+impl KeyScale {
+    pub fn new(scale: Vec3, time_stamp: f32) -> Self {
+        KeyScale {
+            scale,
+            time_stamp,
+            in_tangent: Vec3::ZERO,
+            out_tangent: Vec3::ZERO,
+        }
+    }
+
+    pub fn with_tangents(mut self, in_tangent: Vec3, out_tangent: Vec3) -> Self {
+        self.in_tangent = in_tangent;
+        self.out_tangent = out_tangent;
+        self
+    }
+}
+
+/// The morph target weights of the node's mesh, with a weight for each target. The tangents are
+/// only used by cubic spline channels and have a value for each target.
+#[derive(Debug, Clone)]
+pub struct KeyWeights {
+    pub weights: Vec<f32>,
+    pub time_stamp: f32,
+    pub in_tangents: Vec<f32>,
+    pub out_tangents: Vec<f32>,
+}
+
+impl KeyWeights {
+    pub fn new(weights: Vec<f32>, time_stamp: f32) -> Self {
+        KeyWeights {
+            weights,
+            time_stamp,
+            in_tangents: vec![],
+            out_tangents: vec![],
+        }
+    }
+
+    pub fn with_tangents(mut self, in_tangents: Vec<f32>, out_tangents: Vec<f32>) -> Self {
+        self.in_tangents = in_tangents;
+        self.out_tangents = out_tangents;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,9 +138,18 @@ pub struct NodeAnimation {
     pub positions: Vec<KeyPosition>,
     pub rotations: Vec<KeyRotation>,
     pub scales: Vec<KeyScale>,
+    /// Empty when the channel doesn't animate morph target weights.
+    pub morph_weights: Vec<KeyWeights>,
+    pub position_interpolation: KeyInterpolation,
+    pub rotation_interpolation: KeyInterpolation,
+    pub scale_interpolation: KeyInterpolation,
+    pub morph_weight_interpolation: KeyInterpolation,
+    pub pre_state: AnimationBehaviour,
+    pub post_state: AnimationBehaviour,
 }
 
 impl NodeAnimation {
+    #[cfg(feature = "russimp")]
     pub fn new(name: &str, channel: &NodeAnim) -> Self {
         let positions: Vec<KeyPosition> = channel.position_keys.iter().map(|key| key.into()).collect();
         let rotations: Vec<KeyRotation> = channel.rotation_keys.iter().map(|key| key.into()).collect();
@@ -39,15 +158,87 @@ impl NodeAnimation {
         let name = name.into();
         debug!("NodeAnimation: {}", &name);
 
+        NodeAnimation::from_keys(name, positions, rotations, scales)
+            .with_pre_state((&channel.pre_state).into())
+            .with_post_state((&channel.post_state).into())
+    }
+
+    /// Linear interpolation, holding the first and last keys outside of them. A channel without keys
+    /// stays at the identity.
+    pub fn from_keys(name: Rc<str>, positions: Vec<KeyPosition>, rotations: Vec<KeyRotation>, scales: Vec<KeyScale>) -> Self {
         NodeAnimation {
             name,
             positions,
             rotations,
             scales,
+            morph_weights: vec![],
+            position_interpolation: KeyInterpolation::Linear,
+            rotation_interpolation: KeyInterpolation::Linear,
+            scale_interpolation: KeyInterpolation::Linear,
+            morph_weight_interpolation: KeyInterpolation::Linear,
+            pre_state: AnimationBehaviour::Constant,
+            post_state: AnimationBehaviour::Constant,
+        }
+    }
+
+    /// Sets the interpolation of all the channels.
+    pub fn with_interpolation(mut self, interpolation: KeyInterpolation) -> Self {
+        self.position_interpolation = interpolation;
+        self.rotation_interpolation = interpolation;
+        self.scale_interpolation = interpolation;
+        self.morph_weight_interpolation = interpolation;
+        self
+    }
+
+    pub fn with_pre_state(mut self, pre_state: AnimationBehaviour) -> Self {
+        self.pre_state = pre_state;
+        self
+    }
+
+    pub fn with_post_state(mut self, post_state: AnimationBehaviour) -> Self {
+        self.post_state = post_state;
+        self
+    }
+
+    /// The keys as deltas from `reference`. A delta is applied by adding its translation, multiplying
+    /// the base rotation by its rotation and the base scale by its scale.
+    pub fn to_additive(&self, reference: &Transform) -> NodeAnimation {
+        let inverse_rotation = reference.rotation.inverse();
+
+        NodeAnimation {
+            name: self.name.clone(),
+            positions: self
+                .positions
+                .iter()
+                .map(|key| {
+                    KeyPosition::new(key.position - reference.translation, key.time_stamp).with_tangents(key.in_tangent, key.out_tangent)
+                })
+                .collect(),
+            rotations: self
+                .rotations
+                .iter()
+                .map(|key| {
+                    KeyRotation::new((inverse_rotation * key.orientation).normalize(), key.time_stamp).with_tangents(
+                        Vec4::from(inverse_rotation * Quat::from_vec4(key.in_tangent)),
+                        Vec4::from(inverse_rotation * Quat::from_vec4(key.out_tangent)),
+                    )
+                })
+                .collect(),
+            scales: self
+                .scales
+                .iter()
+                .map(|key| {
+                    KeyScale::new(key.scale / reference.scale, key.time_stamp)
+                        .with_tangents(key.in_tangent / reference.scale, key.out_tangent / reference.scale)
+                })
+                .collect(),
+            ..self.clone()
         }
     }
 
     pub fn get_animation_transform(&self, animation_time: f32) -> Transform {
+        let animation_time = self.channel_time(animation_time);
+
         Transform {
             translation: self.interpolate_position(animation_time),
             rotation: self.interpolate_rotation(animation_time),
@@ -55,118 +246,352 @@ impl NodeAnimation {
         }
     }
 
-    fn interpolate_position(&self, animation_time: f32) -> Vec3 {
-        if self.positions.len() == 1 {
-            return self.positions[0].position;
-        }
+    /// Moves times outside of the keys back inside them when the channel repeats.
+    fn channel_time(&self, animation_time: f32) -> f32 {
+        let first_times = [
+            self.positions.first().map(|key| key.time_stamp),
+            self.rotations.first().map(|key| key.time_stamp),
+            self.scales.first().map(|key| key.time_stamp),
+            self.morph_weights.first().map(|key| key.time_stamp),
+        ];
+        let last_times = [
+            self.positions.last().map(|key| key.time_stamp),
+            self.rotations.last().map(|key| key.time_stamp),
+            self.scales.last().map(|key| key.time_stamp),
+            self.morph_weights.last().map(|key| key.time_stamp),
+        ];
+        let start = first_times.into_iter().flatten().fold(f32::MAX, f32::min);
+        let end = last_times.into_iter().flatten().fold(f32::MIN, f32::max);
 
-        let p0_index = self.get_position_index(animation_time);
-        let p1_index = p0_index + 1;
+        let length = end - start;
+        if length <= 0.0 {
+            return animation_time;
+        }
 
-        let scale_factor = self.get_scale_factor(
-            self.positions[p0_index].time_stamp,
-            self.positions[p1_index].time_stamp,
-            animation_time,
-        );
+        let repeats = (animation_time < start && self.pre_state == AnimationBehaviour::Repeat)
+            || (animation_time > end && self.post_state == AnimationBehaviour::Repeat);
 
-        // final_position
-        self.positions[p0_index]
-            .position
-            .lerp(self.positions[p1_index].position, scale_factor)
+        if repeats {
+            start + (animation_time - start).rem_euclid(length)
+        } else {
+            animation_time
+        }
     }
 
-    fn interpolate_rotation(&self, animation_time: f32) -> Quat {
-        if self.rotations.len() == 1 {
-            let rotation = self.rotations[0].orientation.normalize();
-            return rotation;
+    /// The morph target weights at the time, None when the channel doesn't animate them.
+    pub fn get_morph_weights(&self, animation_time: f32) -> Option<Vec<f32>> {
+        if self.morph_weights.len() <= 1 {
+            return self.morph_weights.first().map(|key| key.weights.clone());
         }
 
-        let p0_index = self.get_rotation_index(animation_time);
-        let p1_index = p0_index + 1;
+        let animation_time = self.channel_time(animation_time);
+        let (p0_index, p1_index, scale_factor) = key_frames(&self.morph_weights, animation_time, |key| key.time_stamp);
+        let (p0, p1) = (&self.morph_weights[p0_index], &self.morph_weights[p1_index]);
 
-        let scale_factor = self.get_scale_factor(
-            self.rotations[p0_index].time_stamp,
-            self.rotations[p1_index].time_stamp,
-            animation_time,
-        );
+        let weights = match self.morph_weight_interpolation {
+            KeyInterpolation::Linear => p0
+                .weights
+                .iter()
+                .zip(&p1.weights)
+                .map(|(w0, w1)| w0 + (w1 - w0) * scale_factor)
+                .collect(),
+            KeyInterpolation::Step => p0.weights.clone(),
+            KeyInterpolation::CubicSpline => (0..p0.weights.len().min(p1.weights.len()))
+                .map(|i| {
+                    cubic_spline(
+                        (p0.weights[i], p0.out_tangents.get(i).copied().unwrap_or_default()),
+                        (p1.weights[i], p1.in_tangents.get(i).copied().unwrap_or_default()),
+                        p1.time_stamp - p0.time_stamp,
+                        scale_factor,
+                    )
+                })
+                .collect(),
+        };
 
-        // final_rotation
-        self.rotations[p0_index]
-            .orientation
-            .slerp(self.rotations[p1_index].orientation, scale_factor)
+        Some(weights)
     }
 
-    fn interpolate_scaling(&self, animation_time: f32) -> Vec3 {
-        if self.scales.len() == 1 {
-            return self.scales[0].scale;
+    fn interpolate_position(&self, animation_time: f32) -> Vec3 {
+        match self.positions.as_slice() {
+            [] => return Vec3::ZERO,
+            [key] => return key.position,
+            _ => {}
         }
 
-        let p0_index = self.get_scale_index(animation_time);
-        let p1_index = p0_index + 1;
-
-        let scale_factor = self.get_scale_factor(self.scales[p0_index].time_stamp, self.scales[p1_index].time_stamp, animation_time);
+        let (p0_index, p1_index, scale_factor) = key_frames(&self.positions, animation_time, |key| key.time_stamp);
+        let (p0, p1) = (&self.positions[p0_index], &self.positions[p1_index]);
 
-        // final_scale
-        self.scales[p0_index].scale.lerp(self.scales[p1_index].scale, scale_factor)
+        // final_position
+        match self.position_interpolation {
+            KeyInterpolation::Linear => p0.position.lerp(p1.position, scale_factor),
+            KeyInterpolation::Step => p0.position,
+            KeyInterpolation::CubicSpline => cubic_spline(
+                (p0.position, p0.out_tangent),
+                (p1.position, p1.in_tangent),
+                p1.time_stamp - p0.time_stamp,
+                scale_factor,
+            ),
+        }
     }
 
-    fn get_position_index(&self, animation_time: f32) -> usize {
-        for index in 0..self.positions.len() - 1 {
-            if animation_time < self.positions[index + 1].time_stamp {
-                return index;
-            }
+    fn interpolate_rotation(&self, animation_time: f32) -> Quat {
+        match self.rotations.as_slice() {
+            [] => return Quat::IDENTITY,
+            [key] => return key.orientation.normalize(),
+            _ => {}
         }
-        panic!("animation time out of bounds");
-    }
 
-    fn get_rotation_index(&self, animation_time: f32) -> usize {
-        for index in 0..self.rotations.len() - 1 {
-            if animation_time < self.rotations[index + 1].time_stamp {
-                return index;
-            }
+        let (p0_index, p1_index, scale_factor) = key_frames(&self.rotations, animation_time, |key| key.time_stamp);
+        let (p0, p1) = (&self.rotations[p0_index], &self.rotations[p1_index]);
+
+        // final_rotation
+        match self.rotation_interpolation {
+            KeyInterpolation::Linear => p0.orientation.slerp(p1.orientation, scale_factor),
+            KeyInterpolation::Step => p0.orientation.normalize(),
+            KeyInterpolation::CubicSpline => Quat::from_vec4(cubic_spline(
+                (Vec4::from(p0.orientation), p0.out_tangent),
+                (Vec4::from(p1.orientation), p1.in_tangent),
+                p1.time_stamp - p0.time_stamp,
+                scale_factor,
+            ))
+            .normalize(),
         }
-        panic!("animation time out of bounds");
     }
 
-    fn get_scale_index(&self, animation_time: f32) -> usize {
-        for index in 0..self.scales.len() - 1 {
-            if animation_time < self.scales[index + 1].time_stamp {
-                return index;
-            }
+    fn interpolate_scaling(&self, animation_time: f32) -> Vec3 {
+        match self.scales.as_slice() {
+            [] => return Vec3::ONE,
+            [key] => return key.scale,
+            _ => {}
+        }
+
+        let (p0_index, p1_index, scale_factor) = key_frames(&self.scales, animation_time, |key| key.time_stamp);
+        let (p0, p1) = (&self.scales[p0_index], &self.scales[p1_index]);
+
+        // final_scale
+        match self.scale_interpolation {
+            KeyInterpolation::Linear => p0.scale.lerp(p1.scale, scale_factor),
+            KeyInterpolation::Step => p0.scale,
+            KeyInterpolation::CubicSpline => cubic_spline(
+                (p0.scale, p0.out_tangent),
+                (p1.scale, p1.in_tangent),
+                p1.time_stamp - p0.time_stamp,
+                scale_factor,
+            ),
         }
-        panic!("animation time out of bounds");
     }
+}
+
+/// The glTF cubic Hermite spline from a value and its out tangent to the next value and its in tangent,
+/// with the tangents scaled by the time between the keys.
+fn cubic_spline<T>((v0, out_tangent): (T, T), (v1, in_tangent): (T, T), key_delta: f32, t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    v0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + out_tangent * ((t3 - 2.0 * t2 + t) * key_delta)
+        + v1 * (-2.0 * t3 + 3.0 * t2)
+        + in_tangent * ((t3 - t2) * key_delta)
+}
 
-    fn get_scale_factor(&self, last_timestamp: f32, next_timestamp: f32, animation_time: f32) -> f32 {
-        let mid_way_length = animation_time - last_timestamp;
-        let frames_diff = next_timestamp - last_timestamp;
-        mid_way_length / frames_diff
+/// The indexes of the keys either side of the time and how far the time is from the first to the second.
+/// Keys are sorted by time, so they are binary searched. Times before the first key or after the last clamp to that key.
+fn key_frames<K>(keys: &[K], animation_time: f32, time_stamp: impl Fn(&K) -> f32) -> (usize, usize, f32) {
+    let next_index = keys.partition_point(|key| time_stamp(key) <= animation_time);
+
+    if next_index == 0 {
+        return (0, 0, 0.0);
+    }
+    if next_index == keys.len() {
+        return (next_index - 1, next_index - 1, 0.0);
     }
+
+    let last_index = next_index - 1;
+    let last_timestamp = time_stamp(&keys[last_index]);
+    let next_timestamp = time_stamp(&keys[next_index]);
+
+    (
+        last_index,
+        next_index,
+        (animation_time - last_timestamp) / (next_timestamp - last_timestamp),
+    )
 }
 
+#[cfg(feature = "russimp")]
 impl From<&VectorKey> for KeyPosition {
     fn from(vector_key: &VectorKey) -> Self {
-        KeyPosition {
-            position: vector_key.value,
-            time_stamp: vector_key.time as f32,
-        }
+        KeyPosition::new(vector_key.value, vector_key.time as f32)
     }
 }
 
+#[cfg(feature = "russimp")]
 impl From<&QuatKey> for KeyRotation {
     fn from(quad_key: &QuatKey) -> Self {
-        KeyRotation {
-            orientation: quad_key.value,
-            time_stamp: quad_key.time as f32,
-        }
+        KeyRotation::new(quad_key.value, quad_key.time as f32)
     }
 }
 
+#[cfg(feature = "russimp")]
 impl From<&VectorKey> for KeyScale {
     fn from(vector_key: &VectorKey) -> Self {
-        KeyScale {
-            scale: vector_key.value,
-            time_stamp: vector_key.time as f32,
+        KeyScale::new(vector_key.value, vector_key.time as f32)
+    }
+}
+
+/// Linear extrapolation isn't supported, it holds the nearest key like the default does.
+#[cfg(feature = "russimp")]
+impl From<&AnimBehaviour> for AnimationBehaviour {
+    fn from(behaviour: &AnimBehaviour) -> Self {
+        match behaviour {
+            AnimBehaviour::Repeat => AnimationBehaviour::Repeat,
+            _ => AnimationBehaviour::Constant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node_animation::{AnimationBehaviour, KeyInterpolation, KeyPosition, KeyRotation, KeyScale, KeyWeights, NodeAnimation};
+    use glam::{Quat, Vec3};
+    use std::rc::Rc;
+
+    fn node_animation() -> NodeAnimation {
+        NodeAnimation::from_keys(
+            Rc::from("node"),
+            (0..100)
+                .map(|i| KeyPosition::new(Vec3::new(i as f32, 0.0, 0.0), i as f32 * 2.0 + 10.0))
+                .collect(),
+            vec![KeyRotation::new(Quat::IDENTITY, 0.0)],
+            vec![KeyScale::new(Vec3::ONE, 0.0), KeyScale::new(Vec3::splat(2.0), 10.0)],
+        )
+    }
+
+    /// Moves from 0 to 1 along x over 10 ticks.
+    fn two_keys(interpolation: KeyInterpolation) -> NodeAnimation {
+        NodeAnimation::from_keys(
+            Rc::from("node"),
+            vec![KeyPosition::new(Vec3::ZERO, 0.0), KeyPosition::new(Vec3::X, 10.0)],
+            vec![KeyRotation::new(Quat::IDENTITY, 0.0)],
+            vec![KeyScale::new(Vec3::ONE, 0.0)],
+        )
+        .with_interpolation(interpolation)
+    }
+
+    #[test]
+    fn test_interpolates_between_keys() {
+        let node_animation = node_animation();
+
+        let transform = node_animation.get_animation_transform(51.0);
+        assert!(transform.translation.abs_diff_eq(Vec3::new(20.5, 0.0, 0.0), 1e-5));
+
+        let transform = node_animation.get_animation_transform(5.0);
+        assert!(transform.scale.abs_diff_eq(Vec3::splat(1.5), 1e-5));
+    }
+
+    #[test]
+    fn test_clamps_outside_keys() {
+        let node_animation = node_animation();
+
+        let transform = node_animation.get_animation_transform(0.0);
+        assert_eq!(transform.translation, Vec3::ZERO);
+
+        // at and past the last key
+        let transform = node_animation.get_animation_transform(208.0);
+        assert_eq!(transform.translation, Vec3::new(99.0, 0.0, 0.0));
+        assert_eq!(transform.scale, Vec3::splat(2.0));
+
+        let transform = node_animation.get_animation_transform(1000.0);
+        assert_eq!(transform.translation, Vec3::new(99.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_step_holds_keys() {
+        let node_animation = two_keys(KeyInterpolation::Step);
+
+        assert_eq!(node_animation.get_animation_transform(9.9).translation, Vec3::ZERO);
+        assert_eq!(node_animation.get_animation_transform(10.0).translation, Vec3::X);
+    }
+
+    #[test]
+    fn test_cubic_spline_tangents() {
+        // without tangents the spline eases in and out
+        let node_animation = two_keys(KeyInterpolation::CubicSpline);
+        assert!(node_animation
+            .get_animation_transform(5.0)
+            .translation
+            .abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+        assert!(node_animation.get_animation_transform(2.0).translation.x < 0.2);
+
+        // tangents matching the slope of the line give the line
+        let mut node_animation = two_keys(KeyInterpolation::CubicSpline);
+        for key in node_animation.positions.iter_mut() {
+            *key = key.clone().with_tangents(Vec3::X * 0.1, Vec3::X * 0.1);
         }
+        assert!(node_animation
+            .get_animation_transform(2.0)
+            .translation
+            .abs_diff_eq(Vec3::new(0.2, 0.0, 0.0), 1e-5));
+
+        // rotations stay normalized
+        let mut node_animation = two_keys(KeyInterpolation::CubicSpline);
+        node_animation.rotations = vec![
+            KeyRotation::new(Quat::IDENTITY, 0.0),
+            KeyRotation::new(Quat::from_rotation_y(1.0), 10.0),
+        ];
+        let rotation = node_animation.get_animation_transform(3.0).rotation;
+        assert!(rotation.is_normalized());
+        assert!(rotation.angle_between(Quat::IDENTITY) > 0.0);
+    }
+
+    #[test]
+    fn test_pre_and_post_state() {
+        let node_animation = two_keys(KeyInterpolation::Linear);
+        assert_eq!(node_animation.get_animation_transform(-5.0).translation, Vec3::ZERO);
+        assert_eq!(node_animation.get_animation_transform(15.0).translation, Vec3::X);
+
+        let node_animation = node_animation
+            .with_pre_state(AnimationBehaviour::Repeat)
+            .with_post_state(AnimationBehaviour::Repeat);
+        assert!(node_animation
+            .get_animation_transform(-2.0)
+            .translation
+            .abs_diff_eq(Vec3::new(0.8, 0.0, 0.0), 1e-5));
+        assert!(node_animation
+            .get_animation_transform(23.0)
+            .translation
+            .abs_diff_eq(Vec3::new(0.3, 0.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_empty_channels() {
+        let node_animation = NodeAnimation::from_keys(Rc::from("node"), vec![], vec![], vec![]);
+        let transform = node_animation.get_animation_transform(5.0);
+        assert_eq!(transform.translation, Vec3::ZERO);
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+        assert_eq!(transform.scale, Vec3::ONE);
+
+        // the other channels still animate
+        let mut node_animation = two_keys(KeyInterpolation::Linear);
+        node_animation.rotations.clear();
+        node_animation.scales.clear();
+        let transform = node_animation.get_animation_transform(5.0);
+        assert!(transform.translation.abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-5));
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+    }
+
+    #[test]
+    fn test_morph_weights() {
+        let mut node_animation = two_keys(KeyInterpolation::Linear);
+        assert_eq!(node_animation.get_morph_weights(5.0), None);
+
+        node_animation.morph_weights = vec![KeyWeights::new(vec![0.0, 1.0], 0.0), KeyWeights::new(vec![1.0, 0.0], 10.0)];
+        let weights = node_animation.get_morph_weights(2.5).unwrap();
+        assert!((weights[0] - 0.25).abs() < 1e-5 && (weights[1] - 0.75).abs() < 1e-5);
+
+        node_animation.morph_weight_interpolation = KeyInterpolation::Step;
+        assert_eq!(node_animation.get_morph_weights(9.0), Some(vec![0.0, 1.0]));
     }
 }
@@ -0,0 +1,247 @@
+use crate::hash_map::HashMap;
+use crate::model_animation::NodeData;
+use crate::transform::Transform;
+use glam::{Quat, Vec3, Vec4};
+use std::rc::Rc;
+
+/// A node of the flattened hierarchy, parents come before their children.
+#[derive(Debug, Clone)]
+pub struct PoseNode {
+    pub name: Rc<str>,
+    pub parent: Option<usize>,
+    /// The rest transform, relative to the parent.
+    pub transform: Transform,
+    pub meshes: Rc<Vec<u32>>,
+}
+
+/// Flattens the hierarchy depth first.
+pub fn flatten_nodes(root_node: &NodeData) -> Vec<PoseNode> {
+    let mut nodes = vec![];
+    push_node(root_node, None, &mut nodes);
+    nodes
+}
+
+fn push_node(node_data: &NodeData, parent: Option<usize>, nodes: &mut Vec<PoseNode>) {
+    let index = nodes.len();
+
+    nodes.push(PoseNode {
+        name: node_data.name.clone(),
+        parent,
+        transform: node_data.transform,
+        meshes: node_data.meshes.clone(),
+    });
+
+    for child_node in node_data.children.iter() {
+        push_node(child_node, Some(index), nodes);
+    }
+}
+
+/// The global transforms of the local pose, with `root_transform` as the parent of the root node.
+pub fn global_pose(pose_nodes: &[PoseNode], local_pose: &[Transform], root_transform: Transform) -> Vec<Transform> {
+    let mut global_transforms: Vec<Transform> = Vec::with_capacity(local_pose.len());
+
+    for (pose_node, local) in pose_nodes.iter().zip(local_pose) {
+        let parent_transform = pose_node.parent.map_or(root_transform, |parent| global_transforms[parent]);
+        global_transforms.push(parent_transform.mul_transform(*local));
+    }
+
+    global_transforms
+}
+
+/// Accumulates weighted local transforms for each node. Translations and scales are averaged by weight
+/// and rotations by the normalized weighted sum of the quaternions, flipped into the same hemisphere.
+/// The result doesn't depend on the order the samples are added in and the weights don't need to sum to 1.
+#[derive(Debug, Clone)]
+pub struct PoseBlend {
+    translations: Vec<Vec3>,
+    rotations: Vec<Vec4>,
+    scales: Vec<Vec3>,
+    weights: Vec<f32>,
+    morph_weights: Vec<Vec<f32>>,
+    morph_totals: Vec<f32>,
+}
+
+impl PoseBlend {
+    pub fn new(node_count: usize) -> Self {
+        PoseBlend {
+            translations: vec![Vec3::ZERO; node_count],
+            rotations: vec![Vec4::ZERO; node_count],
+            scales: vec![Vec3::ZERO; node_count],
+            weights: vec![0.0; node_count],
+            morph_weights: vec![vec![]; node_count],
+            morph_totals: vec![0.0; node_count],
+        }
+    }
+
+    pub fn add(&mut self, index: usize, transform: Transform, weight: f32) {
+        if weight <= 0.0 {
+            return;
+        }
+
+        // q and -q are the same rotation, summing them needs them on the same side
+        let mut rotation = Vec4::from(transform.rotation);
+        if self.rotations[index].dot(rotation) < 0.0 {
+            rotation = -rotation;
+        }
+
+        self.translations[index] += transform.translation * weight;
+        self.rotations[index] += rotation * weight;
+        self.scales[index] += transform.scale * weight;
+        self.weights[index] += weight;
+    }
+
+    /// Accumulates the node's morph target weights. They are averaged by the node's weight, so the samples without
+    /// morph target weights count as zero weights and the morph targets of a clip that fades out fade out with it.
+    pub fn add_morph_weights(&mut self, index: usize, morph_weights: &[f32], weight: f32) {
+        if weight <= 0.0 {
+            return;
+        }
+
+        let blended = &mut self.morph_weights[index];
+        if blended.len() < morph_weights.len() {
+            blended.resize(morph_weights.len(), 0.0);
+        }
+
+        for (blended, morph_weight) in blended.iter_mut().zip(morph_weights) {
+            *blended += morph_weight * weight;
+        }
+        self.morph_totals[index] += weight;
+    }
+
+    /// The blended morph target weights of the node, None when none were added for it.
+    pub fn get_morph_weights(&self, index: usize) -> Option<Vec<f32>> {
+        let weight = self.weights[index];
+        if self.morph_totals[index] <= 0.0 || weight <= 0.0 {
+            return None;
+        }
+
+        Some(self.morph_weights[index].iter().map(|morph_weight| morph_weight / weight).collect())
+    }
+
+    /// The blended transform of the node, None when nothing was added for it.
+    pub fn get(&self, index: usize) -> Option<Transform> {
+        let weight = self.weights[index];
+        if weight <= 0.0 {
+            return None;
+        }
+
+        Some(Transform {
+            translation: self.translations[index] / weight,
+            rotation: Quat::from_vec4(self.rotations[index]).normalize(),
+            scale: self.scales[index] / weight,
+        })
+    }
+}
+
+/// A local pose taken out of an animator, such as the pose a character is in when it dies. The animator can
+/// blend from it into a clip, or hold it, changed by code, as a procedural pose.
+#[derive(Debug, Clone)]
+pub struct CapturedPose {
+    /// The node names, in the order of the animator's pose nodes.
+    pub node_names: Vec<Rc<str>>,
+    /// The local transforms of the nodes.
+    pub local_transforms: Vec<Transform>,
+    /// The morph target weights of each node's meshes, by node name.
+    pub morph_weights: HashMap<Rc<str>, Vec<f32>>,
+}
+
+impl CapturedPose {
+    pub fn get_local_transform(&self, node_name: &str) -> Option<Transform> {
+        let index = self.node_names.iter().position(|name| name.as_ref() == node_name)?;
+        Some(self.local_transforms[index])
+    }
+
+    /// Sets the local transform of the node, nodes the pose doesn't have are ignored.
+    pub fn set_local_transform(&mut self, node_name: &str, transform: Transform) {
+        if let Some(index) = self.node_names.iter().position(|name| name.as_ref() == node_name) {
+            self.local_transforms[index] = transform;
+        }
+    }
+
+    /// Adds the pose to the blend, skipping the nodes that don't match the pose nodes,
+    /// such as those of a pose captured from another skeleton.
+    pub fn add_to_blend(&self, pose_nodes: &[PoseNode], pose: &mut PoseBlend, weight: f32) {
+        for (index, pose_node) in pose_nodes.iter().enumerate() {
+            if self.node_names.get(index) != Some(&pose_node.name) {
+                continue;
+            }
+
+            pose.add(index, self.local_transforms[index], weight);
+
+            if let Some(morph_weights) = self.morph_weights.get(&pose_node.name) {
+                pose.add_morph_weights(index, morph_weights, weight);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_animation::NodeData;
+    use crate::pose::{flatten_nodes, PoseBlend};
+    use crate::transform::Transform;
+    use glam::{Quat, Vec3};
+    use std::f32::consts::FRAC_PI_2;
+    use std::rc::Rc;
+
+    fn node(name: &str, children: Vec<NodeData>) -> NodeData {
+        NodeData {
+            name: Rc::from(name),
+            transform: Transform::IDENTITY,
+            children,
+            meshes: Rc::new(vec![]),
+        }
+    }
+
+    #[test]
+    fn test_flatten_nodes() {
+        let root = node("root", vec![node("a", vec![node("b", vec![])]), node("c", vec![])]);
+        let nodes = flatten_nodes(&root);
+
+        let names: Vec<&str> = nodes.iter().map(|n| n.name.as_ref()).collect();
+        assert_eq!(names, vec!["root", "a", "b", "c"]);
+
+        let parents: Vec<Option<usize>> = nodes.iter().map(|n| n.parent).collect();
+        assert_eq!(parents, vec![None, Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_blend_is_normalized_and_order_independent() {
+        let a = Transform::from_translation(Vec3::X).with_rotation(Quat::IDENTITY);
+        let b = Transform::from_translation(Vec3::Y).with_rotation(Quat::from_rotation_z(FRAC_PI_2));
+        let c = Transform::from_translation(Vec3::Z).with_rotation(Quat::from_rotation_x(FRAC_PI_2));
+
+        let mut forward = PoseBlend::new(1);
+        forward.add(0, a, 1.0);
+        forward.add(0, b, 2.0);
+        forward.add(0, c, 1.0);
+
+        let mut backward = PoseBlend::new(1);
+        backward.add(0, c, 0.25);
+        backward.add(0, b, 0.5);
+        backward.add(0, a, 0.25);
+
+        let forward = forward.get(0).unwrap();
+        let backward = backward.get(0).unwrap();
+
+        assert!(forward.translation.abs_diff_eq(Vec3::new(0.25, 0.5, 0.25), 1e-5));
+        assert!(forward.translation.abs_diff_eq(backward.translation, 1e-5));
+        assert!(forward.rotation.abs_diff_eq(backward.rotation, 1e-5));
+        assert!(forward.rotation.is_normalized());
+    }
+
+    #[test]
+    fn test_blend_hemisphere() {
+        let rotation = Quat::from_rotation_y(0.5);
+
+        // the same rotation with opposite signs would cancel out without the hemisphere correction
+        let mut blend = PoseBlend::new(2);
+        blend.add(0, Transform::from_rotation(rotation), 0.5);
+        blend.add(0, Transform::from_rotation(-rotation), 0.5);
+
+        let blended = blend.get(0).unwrap().rotation;
+        assert!(blended.angle_between(rotation) < 1e-3);
+
+        assert!(blend.get(1).is_none());
+    }
+}
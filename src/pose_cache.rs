@@ -0,0 +1,150 @@
+use crate::animator::NodeTransform;
+use crate::dual_quat::DualQuat;
+use crate::hash_map::{FixedState, HashMap};
+use crate::model_animation::{BoneData, ModelAnimation};
+use crate::pose::PoseNode;
+use crate::skinning::SkinningMethod;
+use crate::transform::Transform;
+use glam::Mat4;
+use std::cell::{Cell, RefCell};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::rc::Rc;
+
+/// Identifies a sampled pose. Animations are told apart by identity rather than by name, which repeats in
+/// glTF files and between an animation and its retargeted copies, so models share poses when their animators
+/// share the animation, such as clones of one animator. The skeleton id keeps models of different skeletons
+/// apart when they play the same animation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoseKey {
+    /// See `skeleton_id`.
+    pub skeleton_id: u64,
+    /// See `animation_id`.
+    pub animation_id: usize,
+    /// The tick quantized to the cache's samples per second.
+    pub sample: i64,
+    /// The root motion node and the bits of the clip's start tick, the pose the node is held at.
+    pub root_motion: Option<(Rc<str>, u32)>,
+    pub skinning_method: SkinningMethod,
+}
+
+/// A hash of the node hierarchy with its rest transforms and of the bones with their offsets, the same
+/// for every model loaded from the same file.
+pub fn skeleton_id<'a>(pose_nodes: &[PoseNode], bones: impl IntoIterator<Item = &'a BoneData>) -> u64 {
+    let mut hasher = FixedState.build_hasher();
+
+    for pose_node in pose_nodes {
+        pose_node.name.hash(&mut hasher);
+        pose_node.parent.hash(&mut hasher);
+        pose_node.meshes.hash(&mut hasher);
+        hash_transform(&pose_node.transform, &mut hasher);
+    }
+
+    let mut bones: Vec<&BoneData> = bones.into_iter().collect();
+    bones.sort_by_key(|bone_data| bone_data.bone_index);
+
+    for bone_data in bones {
+        bone_data.name.hash(&mut hasher);
+        bone_data.bone_index.hash(&mut hasher);
+        hash_transform(&bone_data.offset_transform, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// The address of the shared animation, which stays the animation's while a pose of it is cached.
+pub fn animation_id(animation: &Rc<ModelAnimation>) -> usize {
+    Rc::as_ptr(animation) as usize
+}
+
+fn hash_transform(transform: &Transform, hasher: &mut impl Hasher) {
+    let components = transform
+        .translation
+        .to_array()
+        .into_iter()
+        .chain(transform.rotation.to_array())
+        .chain(transform.scale.to_array());
+
+    for component in components {
+        component.to_bits().hash(hasher);
+    }
+}
+
+/// Everything an animator update produces for one pose.
+#[derive(Debug)]
+pub struct CachedPose {
+    /// Keeps the address the pose is keyed on, see `animation_id`, from being reused by another animation.
+    pub animation: Rc<ModelAnimation>,
+    pub local_pose: Vec<Transform>,
+    pub node_transforms: HashMap<Rc<str>, NodeTransform>,
+    pub final_bone_matrices: Box<[Mat4]>,
+    pub final_bone_dual_quats: Box<[DualQuat]>,
+    pub final_node_matrices: Box<[Mat4]>,
+    pub morph_weights: HashMap<Rc<str>, Vec<f32>>,
+}
+
+/// Sampled poses shared by the animators of models playing the same animation, such as a crowd of identical enemies.
+/// Ticks are quantized to `samples_per_second`, so animators at nearly the same point of an animation share one pose
+/// and each animation is sampled at most `samples_per_second` times per second of its length. Only animators playing
+/// a single clip use the cache, transitions, layers and IK constraints are sampled by each animator.
+#[derive(Debug)]
+pub struct PoseCache {
+    pub samples_per_second: f32,
+    poses: RefCell<HashMap<PoseKey, Rc<CachedPose>>>,
+    hits: Cell<usize>,
+    misses: Cell<usize>,
+}
+
+impl PoseCache {
+    pub fn new(samples_per_second: f32) -> Self {
+        PoseCache {
+            samples_per_second: samples_per_second.max(f32::EPSILON),
+            poses: HashMap::new().into(),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    /// The sample index of the tick and the tick it's sampled at.
+    pub fn quantize(&self, tick: f32, ticks_per_second: f32) -> (i64, f32) {
+        let ticks_per_sample = ticks_per_second / self.samples_per_second;
+        let sample = (tick / ticks_per_sample).round() as i64;
+        (sample, sample as f32 * ticks_per_sample)
+    }
+
+    pub fn get(&self, key: &PoseKey) -> Option<Rc<CachedPose>> {
+        let pose = self.poses.borrow().get(key).cloned();
+        match &pose {
+            Some(_) => self.hits.set(self.hits.get() + 1),
+            None => self.misses.set(self.misses.get() + 1),
+        }
+        pose
+    }
+
+    pub fn insert(&self, key: PoseKey, pose: CachedPose) {
+        self.poses.borrow_mut().insert(key, Rc::new(pose));
+    }
+
+    /// The number of poses cached.
+    pub fn len(&self) -> usize {
+        self.poses.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.poses.borrow().is_empty()
+    }
+
+    /// Lookups that found a pose.
+    pub fn hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    /// Lookups that had to sample the pose.
+    pub fn misses(&self) -> usize {
+        self.misses.get()
+    }
+
+    /// Drops the cached poses, needed after changing the animations of the models that share the cache.
+    pub fn clear(&self) {
+        self.poses.borrow_mut().clear();
+    }
+}
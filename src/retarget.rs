@@ -0,0 +1,349 @@
+use crate::hash_map::HashMap;
+use crate::model_animation::{BoneData, BoneName, ModelAnimation, NodeData};
+use crate::node_animation::{KeyPosition, KeyRotation, KeyScale, NodeAnimation};
+use crate::pose::{flatten_nodes, PoseNode};
+use crate::transform::Transform;
+use glam::{Quat, Vec4};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The bind pose of a skeleton. Bones are placed by the inverse of their offset transforms and
+/// the other nodes by their node transforms.
+#[derive(Debug, Clone)]
+pub struct BindPose {
+    pub pose_nodes: Vec<PoseNode>,
+    /// Relative to the parent.
+    pub local_transforms: Vec<Transform>,
+    /// Relative to the root node's parent.
+    pub global_transforms: Vec<Transform>,
+}
+
+impl BindPose {
+    pub fn new(root_node: &NodeData, bone_data_map: &HashMap<BoneName, BoneData>) -> Self {
+        let pose_nodes = flatten_nodes(root_node);
+
+        let mut local_transforms = Vec::with_capacity(pose_nodes.len());
+        let mut global_transforms: Vec<Transform> = Vec::with_capacity(pose_nodes.len());
+
+        for pose_node in pose_nodes.iter() {
+            let parent_transform = pose_node.parent.map_or(Transform::IDENTITY, |parent| global_transforms[parent]);
+
+            let (local, global) = match bone_data_map.get(pose_node.name.as_ref()) {
+                Some(bone_data) => {
+                    let global_matrix = bone_data.offset_transform.compute_matrix().inverse();
+                    let local_matrix = parent_transform.compute_matrix().inverse() * global_matrix;
+                    (Transform::from_matrix(local_matrix), Transform::from_matrix(global_matrix))
+                }
+                None => (pose_node.transform, parent_transform.mul_transform(pose_node.transform)),
+            };
+
+            local_transforms.push(local);
+            global_transforms.push(global);
+        }
+
+        BindPose {
+            pose_nodes,
+            local_transforms,
+            global_transforms,
+        }
+    }
+
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        self.pose_nodes.iter().position(|pose_node| pose_node.name.as_ref() == name)
+    }
+
+    /// The global rotation of the node's parent.
+    fn parent_rotation(&self, index: usize) -> Quat {
+        self.pose_nodes[index]
+            .parent
+            .map_or(Quat::IDENTITY, |parent| self.global_transforms[parent].rotation)
+    }
+}
+
+/// Maps the bones of a source skeleton onto a target skeleton so the source's animations can drive the target.
+/// Rotations are carried over as turns from the bind pose, so bones whose bind poses point different ways
+/// still turn the same way. Only the root bone keeps its animated translation, scaled by the relative
+/// leg length, the other bones keep the target's bone lengths.
+#[derive(Debug, Clone, Default)]
+pub struct Retarget {
+    /// Source node names to target node names.
+    pub bone_map: HashMap<Rc<str>, Rc<str>>,
+    /// The source name of the bone that moves the character, usually the hips.
+    pub root_bone: Option<Rc<str>>,
+    /// The source names of the top of the leg and the foot.
+    pub leg: Option<(Rc<str>, Rc<str>)>,
+}
+
+impl Retarget {
+    pub fn new() -> Self {
+        Retarget::default()
+    }
+
+    pub fn map_bone(mut self, source: &str, target: &str) -> Self {
+        self.bone_map.insert(Rc::from(source), Rc::from(target));
+        self
+    }
+
+    /// Maps every source node to the target node with the same name.
+    pub fn map_same_names(mut self, source: &BindPose, target: &BindPose) -> Self {
+        for pose_node in source.pose_nodes.iter() {
+            if target.node_index(&pose_node.name).is_some() {
+                self.bone_map.insert(pose_node.name.clone(), pose_node.name.clone());
+            }
+        }
+        self
+    }
+
+    pub fn with_root_bone(mut self, source: &str) -> Self {
+        self.root_bone = Some(Rc::from(source));
+        self
+    }
+
+    pub fn with_leg(mut self, source_upper_leg: &str, source_foot: &str) -> Self {
+        self.leg = Some((Rc::from(source_upper_leg), Rc::from(source_foot)));
+        self
+    }
+
+    /// The target leg length over the source leg length, or 1.0 without a mapped leg.
+    pub fn translation_scale(&self, source: &BindPose, target: &BindPose) -> f32 {
+        let Some((upper_leg, foot)) = &self.leg else {
+            return 1.0;
+        };
+
+        let leg_length = |bind_pose: &BindPose, upper_leg: &str, foot: &str| -> Option<f32> {
+            let upper_leg = bind_pose.global_transforms[bind_pose.node_index(upper_leg)?].translation;
+            let foot = bind_pose.global_transforms[bind_pose.node_index(foot)?].translation;
+            Some(upper_leg.distance(foot))
+        };
+
+        let source_length = leg_length(source, upper_leg, foot);
+        let target_length = self
+            .bone_map
+            .get(upper_leg)
+            .zip(self.bone_map.get(foot))
+            .and_then(|(upper_leg, foot)| leg_length(target, upper_leg, foot));
+
+        match (source_length, target_length) {
+            (Some(source_length), Some(target_length)) if source_length > 0.0 => target_length / source_length,
+            _ => 1.0,
+        }
+    }
+
+    /// Converts an animation of the source skeleton into one for the target skeleton, to add to the target's animator.
+    pub fn retarget(&self, animation: &ModelAnimation, source: &BindPose, target: &BindPose) -> ModelAnimation {
+        let translation_scale = self.translation_scale(source, target);
+
+        let node_animations = animation
+            .node_animations
+            .borrow()
+            .iter()
+            .filter_map(|node_animation| {
+                let target_name = self.bone_map.get(&node_animation.name)?;
+                let source_index = source.node_index(&node_animation.name)?;
+                let target_index = target.node_index(target_name)?;
+
+                let is_root = self.root_bone.as_ref() == Some(&node_animation.name);
+
+                Some(retarget_node_animation(
+                    node_animation,
+                    target_name,
+                    (source, source_index),
+                    (target, target_index),
+                    is_root.then_some(translation_scale),
+                ))
+            })
+            .collect();
+
+        ModelAnimation {
+            name: animation.name.clone(),
+            duration: animation.duration,
+            ticks_per_second: animation.ticks_per_second,
+            node_animations: RefCell::new(node_animations),
+        }
+    }
+}
+
+/// `root_translation_scale` is set for the root bone, whose translation is carried over.
+fn retarget_node_animation(
+    node_animation: &NodeAnimation,
+    target_name: &Rc<str>,
+    (source, source_index): (&BindPose, usize),
+    (target, target_index): (&BindPose, usize),
+    root_translation_scale: Option<f32>,
+) -> NodeAnimation {
+    let source_local = source.local_transforms[source_index];
+    let target_local = target.local_transforms[target_index];
+
+    // a turn from the source bind pose, moved through the bind pose global frames into the target bone
+    let source_global_rotation = source.global_transforms[source_index].rotation;
+    let target_global_rotation = target.global_transforms[target_index].rotation;
+    let source_to_target = target_global_rotation.inverse() * source_global_rotation;
+
+    let rotations = node_animation
+        .rotations
+        .iter()
+        .map(|key| {
+            // linear in the quaternion, so the tangents move the same way
+            let retarget =
+                |q: Quat| target_local.rotation * source_to_target * source_local.rotation.inverse() * q * source_to_target.inverse();
+            KeyRotation::new(retarget(key.orientation).normalize(), key.time_stamp).with_tangents(
+                Vec4::from(retarget(Quat::from_vec4(key.in_tangent))),
+                Vec4::from(retarget(Quat::from_vec4(key.out_tangent))),
+            )
+        })
+        .collect();
+
+    let positions = match root_translation_scale {
+        Some(scale) => {
+            let source_parent = source.parent_rotation(source_index);
+            let target_parent = target.parent_rotation(target_index);
+            let parent_to_target = target_parent.inverse() * source_parent;
+
+            node_animation
+                .positions
+                .iter()
+                .map(|key| {
+                    KeyPosition::new(
+                        target_local.translation + parent_to_target * (key.position - source_local.translation) * scale,
+                        key.time_stamp,
+                    )
+                    .with_tangents(
+                        parent_to_target * key.in_tangent * scale,
+                        parent_to_target * key.out_tangent * scale,
+                    )
+                })
+                .collect()
+        }
+        None => vec![KeyPosition::new(target_local.translation, 0.0)],
+    };
+
+    let mut retargeted = NodeAnimation::from_keys(
+        target_name.clone(),
+        positions,
+        rotations,
+        vec![KeyScale::new(target_local.scale, 0.0)],
+    );
+    retargeted.rotation_interpolation = node_animation.rotation_interpolation;
+    if root_translation_scale.is_some() {
+        retargeted.position_interpolation = node_animation.position_interpolation;
+    }
+    retargeted.pre_state = node_animation.pre_state;
+    retargeted.post_state = node_animation.post_state;
+    retargeted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash_map::HashMap;
+    use crate::model_animation::{ModelAnimation, NodeData};
+    use crate::node_animation::{KeyPosition, KeyRotation, KeyScale, NodeAnimation};
+    use crate::pose::global_pose;
+    use crate::retarget::{BindPose, Retarget};
+    use crate::transform::Transform;
+    use glam::{Quat, Vec3};
+    use std::cell::RefCell;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+    use std::rc::Rc;
+
+    fn node(name: &str, transform: Transform, children: Vec<NodeData>) -> NodeData {
+        NodeData {
+            name: Rc::from(name),
+            transform,
+            children,
+            meshes: Rc::new(vec![]),
+        }
+    }
+
+    /// Hips at the height of the leg, with a knee half way and a foot on the ground.
+    fn skeleton(prefix: &str, leg_length: f32, knee_rotation: Quat) -> NodeData {
+        let half = leg_length / 2.0;
+        node(
+            &format!("{prefix}hips"),
+            Transform::from_xyz(0.0, leg_length, 0.0),
+            vec![node(
+                &format!("{prefix}knee"),
+                Transform::from_xyz(0.0, -half, 0.0).with_rotation(knee_rotation),
+                vec![node(&format!("{prefix}foot"), Transform::from_xyz(0.0, -half, 0.0), vec![])],
+            )],
+        )
+    }
+
+    fn walk_animation() -> ModelAnimation {
+        let hips = NodeAnimation::from_keys(
+            Rc::from("hips"),
+            vec![
+                KeyPosition::new(Vec3::new(0.0, 2.0, 0.0), 0.0),
+                KeyPosition::new(Vec3::new(0.0, 2.0, 2.0), 10.0),
+            ],
+            vec![KeyRotation::new(Quat::IDENTITY, 0.0)],
+            vec![KeyScale::new(Vec3::ONE, 0.0)],
+        );
+
+        let knee = NodeAnimation::from_keys(
+            Rc::from("knee"),
+            vec![KeyPosition::new(Vec3::new(0.0, -1.0, 0.0), 0.0)],
+            vec![KeyRotation::new(Quat::from_rotation_x(FRAC_PI_4), 0.0)],
+            vec![KeyScale::new(Vec3::ONE, 0.0)],
+        );
+
+        ModelAnimation {
+            name: Rc::from("walk"),
+            duration: 10.0,
+            ticks_per_second: 10.0,
+            node_animations: RefCell::new(vec![hips, knee]),
+        }
+    }
+
+    #[test]
+    fn test_retarget_to_smaller_turned_skeleton() {
+        let source = BindPose::new(&skeleton("", 2.0, Quat::IDENTITY), &HashMap::new());
+
+        // half the size, and the knee's bind pose is turned around the leg
+        let target = BindPose::new(&skeleton("t_", 1.0, Quat::from_rotation_y(FRAC_PI_2)), &HashMap::new());
+
+        let retarget = Retarget::new()
+            .map_bone("hips", "t_hips")
+            .map_bone("knee", "t_knee")
+            .map_bone("foot", "t_foot")
+            .with_root_bone("hips")
+            .with_leg("hips", "foot");
+
+        assert_eq!(retarget.translation_scale(&source, &target), 0.5);
+
+        let animation = retarget.retarget(&walk_animation(), &source, &target);
+        let node_animations = animation.node_animations.borrow();
+        assert_eq!(node_animations.len(), 2);
+
+        let mut local_pose = target.local_transforms.clone();
+        for node_animation in node_animations.iter() {
+            let index = target.node_index(&node_animation.name).unwrap();
+            local_pose[index] = node_animation.get_animation_transform(10.0);
+        }
+
+        let globals = global_pose(&target.pose_nodes, &local_pose, Transform::IDENTITY);
+
+        // the hips move half as far, at the target's height
+        assert!(globals[0].translation.abs_diff_eq(Vec3::new(0.0, 1.0, 1.0), 1e-5));
+
+        // the knee bends forward as in the source, despite its turned bind pose
+        let expected_rotation = Quat::from_rotation_x(FRAC_PI_4) * Quat::from_rotation_y(FRAC_PI_2);
+        assert!(globals[1].rotation.abs_diff_eq(expected_rotation, 1e-5));
+
+        let expected_foot = globals[1].translation + Quat::from_rotation_x(FRAC_PI_4) * Vec3::new(0.0, -0.5, 0.0);
+        assert!(globals[2].translation.abs_diff_eq(expected_foot, 1e-5));
+    }
+
+    #[test]
+    fn test_unmapped_bones_are_skipped() {
+        let source = BindPose::new(&skeleton("", 2.0, Quat::IDENTITY), &HashMap::new());
+        let target = BindPose::new(&skeleton("", 2.0, Quat::IDENTITY), &HashMap::new());
+
+        let retarget = Retarget::new().map_bone("knee", "knee");
+        let animation = retarget.retarget(&walk_animation(), &source, &target);
+        assert_eq!(animation.node_animations.borrow().len(), 1);
+
+        let retarget = Retarget::new().map_same_names(&source, &target);
+        let animation = retarget.retarget(&walk_animation(), &source, &target);
+        assert_eq!(animation.node_animations.borrow().len(), 2);
+    }
+}
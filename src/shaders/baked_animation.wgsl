@@ -0,0 +1,78 @@
+// Skins a vertex from the baked animation palettes, each row a frame of the bone then node matrices.
+// Expects the declarations of baked_palettes and baked_animation.
+
+// the two frames around the time and how far it is between them
+struct BakedFrames {
+    first_row: u32,
+    second_row: u32,
+    blend: f32,
+}
+
+fn baked_matrix(entry: u32, row: u32) -> mat4x4<f32> {
+    let column = i32(entry * 4u);
+    let y = i32(row);
+    return mat4x4<f32>(
+        textureLoad(baked_palettes, vec2<i32>(column, y), 0),
+        textureLoad(baked_palettes, vec2<i32>(column + 1, y), 0),
+        textureLoad(baked_palettes, vec2<i32>(column + 2, y), 0),
+        textureLoad(baked_palettes, vec2<i32>(column + 3, y), 0),
+    );
+}
+
+// the time wraps around the clip's duration, the last frame is at the duration itself
+fn baked_frames(clip_index: u32, time: f32) -> BakedFrames {
+    let clip = baked_animation.clips[min(clip_index, max(baked_animation.clip_count, 1u) - 1u)];
+    let last_frame = max(clip.frame_count, 1u) - 1u;
+
+    var clip_time = 0.0;
+    if (clip.duration > 0.0) {
+        clip_time = time - floor(time / clip.duration) * clip.duration;
+    }
+
+    let frame = min(u32(floor(clip_time * clip.frames_per_second)), last_frame);
+    let next_frame = min(frame + 1u, last_frame);
+
+    let frame_time = f32(frame) / clip.frames_per_second;
+    let interval = min(f32(frame + 1u) / clip.frames_per_second, clip.duration) - frame_time;
+
+    var frames: BakedFrames;
+    frames.first_row = clip.first_row + frame;
+    frames.second_row = clip.first_row + next_frame;
+    frames.blend = 0.0;
+    if (interval > 0.0) {
+        frames.blend = clamp((clip_time - frame_time) / interval, 0.0, 1.0);
+    }
+    return frames;
+}
+
+// the palette entry blended between the frames, bones first then nodes
+fn baked_palette_matrix(entry: u32, frames: BakedFrames) -> mat4x4<f32> {
+    let first = baked_matrix(entry, frames.first_row);
+    let second = baked_matrix(entry, frames.second_row);
+    return first * (1.0 - frames.blend) + second * frames.blend;
+}
+
+// the weighted sum of the vertex's bones at the clip's time, or the mesh's node transform when it has none
+fn baked_skin_matrix(bone_ids: vec4<i32>, weights: vec4<f32>, mesh_index: u32, clip_index: u32, time: f32) -> mat4x4<f32> {
+    let frames = baked_frames(clip_index, time);
+
+    var skin = mat4x4<f32>(vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0));
+    var total_weight = 0.0;
+
+    for (var i = 0u; i < 4u; i++) {
+        let bone_id = bone_ids[i];
+        if (bone_id < 0 || u32(bone_id) >= baked_animation.bone_count || weights[i] <= 0.0) {
+            continue;
+        }
+
+        skin += baked_palette_matrix(u32(bone_id), frames) * weights[i];
+        total_weight += weights[i];
+    }
+
+    if (total_weight <= 0.0) {
+        let node = min(mesh_index, max(baked_animation.node_count, 1u) - 1u);
+        return baked_palette_matrix(baked_animation.bone_count + node, frames);
+    }
+
+    return skin * (1.0 / total_weight);
+}
@@ -0,0 +1,100 @@
+// Skins one mesh's vertices into an output buffer, one invocation per vertex, with the skinning functions of skinning.wgsl.
+// main blends the bone matrices and main_dual_quaternion the bone dual quaternions.
+
+struct DualQuat {
+    real: vec4<f32>,
+    dual: vec4<f32>,
+    scale: vec4<f32>,
+}
+
+struct SkinnedVertex {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+    tangent: vec4<f32>,
+    bi_tangent: vec4<f32>,
+    uv: vec4<f32>,
+}
+
+// ModelVertex is packed, so it's read a word at a time
+const VERTEX_STRIDE: u32 = 22u;
+const OFFSET_OF_NORMAL: u32 = 3u;
+const OFFSET_OF_UV: u32 = 6u;
+const OFFSET_OF_TANGENT: u32 = 8u;
+const OFFSET_OF_BITANGENT: u32 = 11u;
+const OFFSET_OF_BONE_IDS: u32 = 14u;
+const OFFSET_OF_WEIGHTS: u32 = 18u;
+
+@group(0) @binding(0) var<storage, read> node_transforms: array<mat4x4<f32>>;
+@group(0) @binding(1) var<storage, read> bone_transforms: array<mat4x4<f32>>;
+@group(0) @binding(2) var<storage, read> vertices: array<u32>;
+@group(0) @binding(3) var<storage, read> morph_deltas: array<MorphTargetDelta>;
+@group(0) @binding(4) var<storage, read> morph_weights: array<f32>;
+@group(0) @binding(5) var<uniform> mesh_index: u32;
+@group(0) @binding(6) var<storage, read_write> skinned_vertices: array<SkinnedVertex>;
+@group(0) @binding(7) var<storage, read> bone_dual_quats: array<DualQuat>;
+
+fn node_count() -> u32 { return arrayLength(&node_transforms); }
+fn bone_count() -> u32 { return arrayLength(&bone_transforms); }
+fn dual_quat_count() -> u32 { return arrayLength(&bone_dual_quats); }
+
+fn read_float(index: u32) -> f32 {
+    return bitcast<f32>(vertices[index]);
+}
+
+fn read_vec3(index: u32) -> vec3<f32> {
+    return vec3<f32>(read_float(index), read_float(index + 1u), read_float(index + 2u));
+}
+
+fn read_vec4(index: u32) -> vec4<f32> {
+    return vec4<f32>(read_float(index), read_float(index + 1u), read_float(index + 2u), read_float(index + 3u));
+}
+
+fn read_skin_input(vertex_index: u32) -> SkinInput {
+    let base = vertex_index * VERTEX_STRIDE;
+
+    var input: SkinInput;
+    input.position = read_vec3(base);
+    input.normal = read_vec3(base + OFFSET_OF_NORMAL);
+    input.tangent = read_vec3(base + OFFSET_OF_TANGENT);
+    input.bitangent = read_vec3(base + OFFSET_OF_BITANGENT);
+    input.bone_ids = bitcast<vec4<i32>>(vec4<u32>(
+        vertices[base + OFFSET_OF_BONE_IDS],
+        vertices[base + OFFSET_OF_BONE_IDS + 1u],
+        vertices[base + OFFSET_OF_BONE_IDS + 2u],
+        vertices[base + OFFSET_OF_BONE_IDS + 3u],
+    ));
+    input.weights = read_vec4(base + OFFSET_OF_WEIGHTS);
+    input.vertex_index = vertex_index;
+    return input;
+}
+
+fn write_skinned_vertex(vertex_index: u32, skinned: SkinnedGeometry) {
+    let base = vertex_index * VERTEX_STRIDE;
+
+    var output: SkinnedVertex;
+    output.position = skinned.position;
+    output.normal = vec4<f32>(skinned.normal, 0.0);
+    output.tangent = vec4<f32>(skinned.tangent, 0.0);
+    output.bi_tangent = vec4<f32>(skinned.bitangent, 0.0);
+    output.uv = vec4<f32>(read_float(base + OFFSET_OF_UV), read_float(base + OFFSET_OF_UV + 1u), 0.0, 0.0);
+
+    skinned_vertices[vertex_index] = output;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= arrayLength(&skinned_vertices)) {
+        return;
+    }
+
+    write_skinned_vertex(id.x, skin_vertex(read_skin_input(id.x)));
+}
+
+@compute @workgroup_size(64)
+fn main_dual_quaternion(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= arrayLength(&skinned_vertices)) {
+        return;
+    }
+
+    write_skinned_vertex(id.x, skin_vertex_dual_quaternion(read_skin_input(id.x)));
+}
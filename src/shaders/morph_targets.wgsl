@@ -0,0 +1,29 @@
+// Applies a mesh's morph targets to a vertex. Expects the declarations of morph_deltas and morph_weights.
+
+struct MorphTargetDelta {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+    tangent: vec4<f32>,
+}
+
+// the vertex with its morph targets applied, the bitangent isn't morphed
+fn morph_vertex(input: SkinInput) -> SkinInput {
+    var morphed = input;
+
+    let target_count = arrayLength(&morph_weights);
+    let vertex_count = arrayLength(&morph_deltas) / target_count;
+
+    for (var i = 0u; i < target_count; i++) {
+        let weight = morph_weights[i];
+        if (weight == 0.0 || input.vertex_index >= vertex_count) {
+            continue;
+        }
+
+        let delta = morph_deltas[i * vertex_count + input.vertex_index];
+        morphed.position += delta.position.xyz * weight;
+        morphed.normal += delta.normal.xyz * weight;
+        morphed.tangent += delta.tangent.xyz * weight;
+    }
+
+    return morphed;
+}
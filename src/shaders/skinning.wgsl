@@ -0,0 +1,147 @@
+// Skins a vertex with the morph targets and the bone palette. Expects the declarations of
+// node_transforms, bone_transforms, bone_dual_quats, node_count(), bone_count(), dual_quat_count(),
+// mesh_index and morph_vertex(), which morph_targets.wgsl gives for storage buffers.
+
+const MAX_BONE_INFLUENCE: u32 = 4u;
+
+struct SkinInput {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    tangent: vec3<f32>,
+    bitangent: vec3<f32>,
+    bone_ids: vec4<i32>,
+    weights: vec4<f32>,
+    vertex_index: u32,
+}
+
+// in the model's space, before the model transform
+struct SkinnedGeometry {
+    position: vec4<f32>,
+    normal: vec3<f32>,
+    tangent: vec3<f32>,
+    bitangent: vec3<f32>,
+}
+
+fn safe_normalize(v: vec3<f32>) -> vec3<f32> {
+    let length_squared = dot(v, v);
+    if (length_squared <= 0.0) {
+        return v;
+    }
+    return v * inverseSqrt(length_squared);
+}
+
+fn has_bone(bone_id: i32, weight: f32, count: u32) -> bool {
+    // out of range only for the uniform fallback
+    return bone_id >= 0 && u32(bone_id) < count && weight > 0.0;
+}
+
+fn mesh_node_transform() -> mat4x4<f32> {
+    return node_transforms[min(mesh_index, node_count() - 1u)];
+}
+
+fn transform_geometry(transform: mat4x4<f32>, input: SkinInput) -> SkinnedGeometry {
+    let rotation = mat3x3<f32>(transform[0].xyz, transform[1].xyz, transform[2].xyz);
+
+    var skinned: SkinnedGeometry;
+    skinned.position = vec4<f32>((transform * vec4<f32>(input.position, 1.0)).xyz, 1.0);
+    skinned.normal = safe_normalize(rotation * input.normal);
+    skinned.tangent = safe_normalize(rotation * input.tangent);
+    skinned.bitangent = safe_normalize(rotation * input.bitangent);
+    return skinned;
+}
+
+// the weighted sum of the vertex's bones, or the mesh's node transform when it has none
+fn skin_matrix(bone_ids: vec4<i32>, weights: vec4<f32>) -> mat4x4<f32> {
+    var skin = mat4x4<f32>(vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0));
+    var total_weight = 0.0;
+
+    for (var i = 0u; i < MAX_BONE_INFLUENCE; i++) {
+        if (!has_bone(bone_ids[i], weights[i], bone_count())) {
+            continue;
+        }
+
+        skin += bone_transforms[bone_ids[i]] * weights[i];
+        total_weight += weights[i];
+    }
+
+    if (total_weight <= 0.0) {
+        return mesh_node_transform();
+    }
+
+    return skin * (1.0 / total_weight);
+}
+
+// linear blend skinning
+fn skin_vertex(input: SkinInput) -> SkinnedGeometry {
+    return transform_geometry(skin_matrix(input.bone_ids, input.weights), morph_vertex(input));
+}
+
+fn rotate_by_quat(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+    return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+// the weighted sum of the vertex's dual quaternions flipped into the hemisphere of the first and normalized,
+// the real part is zero when the vertex has no bones
+fn blend_dual_quats(bone_ids: vec4<i32>, weights: vec4<f32>) -> DualQuat {
+    var blended: DualQuat;
+    blended.real = vec4<f32>(0.0);
+    blended.dual = vec4<f32>(0.0);
+    blended.scale = vec4<f32>(0.0);
+
+    var first = vec4<f32>(0.0);
+    var total_weight = 0.0;
+
+    for (var i = 0u; i < MAX_BONE_INFLUENCE; i++) {
+        if (!has_bone(bone_ids[i], weights[i], dual_quat_count())) {
+            continue;
+        }
+
+        let dual_quat = bone_dual_quats[bone_ids[i]];
+        if (total_weight == 0.0) {
+            first = dual_quat.real;
+        }
+
+        var weight = weights[i];
+        if (dot(first, dual_quat.real) < 0.0) {
+            weight = -weight;
+        }
+
+        blended.real += dual_quat.real * weight;
+        blended.dual += dual_quat.dual * weight;
+        blended.scale += dual_quat.scale * weights[i];
+        total_weight += weights[i];
+    }
+
+    let length = length(blended.real);
+    if (total_weight <= 0.0 || length <= 0.0) {
+        blended.real = vec4<f32>(0.0);
+        return blended;
+    }
+
+    blended.real /= length;
+    blended.dual /= length;
+    blended.scale /= total_weight;
+    return blended;
+}
+
+// dual quaternion skinning, keeps the volume around twisting joints that linear blend skinning loses
+fn skin_vertex_dual_quaternion(input: SkinInput) -> SkinnedGeometry {
+    let morphed = morph_vertex(input);
+    let blended = blend_dual_quats(input.bone_ids, input.weights);
+
+    if (all(blended.real == vec4<f32>(0.0))) {
+        return transform_geometry(mesh_node_transform(), morphed);
+    }
+
+    let real = blended.real;
+    let dual = blended.dual;
+    let scale = blended.scale.xyz;
+    let translation = 2.0 * (real.w * dual.xyz - dual.w * real.xyz + cross(real.xyz, dual.xyz));
+
+    var skinned: SkinnedGeometry;
+    skinned.position = vec4<f32>(rotate_by_quat(real, morphed.position * scale) + translation, 1.0);
+    skinned.normal = safe_normalize(rotate_by_quat(real, morphed.normal * scale));
+    skinned.tangent = safe_normalize(rotate_by_quat(real, morphed.tangent * scale));
+    skinned.bitangent = safe_normalize(rotate_by_quat(real, morphed.bitangent * scale));
+    return skinned;
+}
@@ -0,0 +1,300 @@
+use crate::gpu_context::GpuContext;
+use crate::model_builder::model_palettes_wgsl;
+use crate::model_mesh::ModelMesh;
+use glam::Vec4;
+use std::mem;
+use wgpu::{BindGroup, BindGroupLayout, Buffer, ComputePipeline};
+
+pub const SKINNING_BIND_GROUP_LAYOUT: &str = "skinning_bind_group_layout";
+
+const WORKGROUP_SIZE: u32 = 64;
+const SKINNING_WGSL: &str = include_str!("shaders/skinning.wgsl");
+const MORPH_TARGETS_WGSL: &str = include_str!("shaders/morph_targets.wgsl");
+const COMPUTE_SKINNING_WGSL: &str = include_str!("shaders/compute_skinning.wgsl");
+const OFFSET_OF_NORMAL: usize = mem::offset_of!(SkinnedVertex, normal);
+const OFFSET_OF_TEXCOORDS: usize = mem::offset_of!(SkinnedVertex, uv);
+const OFFSET_OF_TANGENT: usize = mem::offset_of!(SkinnedVertex, tangent);
+const OFFSET_OF_BITANGENT: usize = mem::offset_of!(SkinnedVertex, bi_tangent);
+
+/// How the bone palette deforms a model's vertices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SkinningMethod {
+    /// Blends the bone matrices, `skin_vertex` in WGSL.
+    #[default]
+    Linear,
+    /// Blends the bones as dual quaternions, `skin_vertex_dual_quaternion` in WGSL. Keeps the volume
+    /// around twisting joints such as shoulders and wrists, which linear blending collapses.
+    DualQuaternion,
+}
+
+/// The WGSL skinning module for vertex shaders, with the model bind group at group 1 and the mesh bind group at group 3.
+/// `skin_vertex` and `skin_vertex_dual_quaternion` apply the morph targets and the bone palette to the position,
+/// normal, tangent and bitangent, and the mesh's node transform to vertices without bones, the same as the compute skinning pass.
+/// `skin_model_vertex` calls the one for `skinning_method`, the model's method, so a shader follows the model's palette.
+/// Without storage buffers in vertex shaders the mesh bind group only has the mesh index and the morph targets aren't applied.
+pub fn skinning_wgsl(storage_palettes: bool, skinning_method: SkinningMethod) -> String {
+    let morph_targets = if storage_palettes {
+        format!(
+            "@group(3) @binding(0) var<storage, read> morph_deltas: array<MorphTargetDelta>;
+@group(3) @binding(1) var<storage, read> morph_weights: array<f32>;
+
+{MORPH_TARGETS_WGSL}"
+        )
+    } else {
+        String::from("fn morph_vertex(input: SkinInput) -> SkinInput { return input; }\n")
+    };
+
+    let skin_function = match skinning_method {
+        SkinningMethod::Linear => "skin_vertex",
+        SkinningMethod::DualQuaternion => "skin_vertex_dual_quaternion",
+    };
+
+    format!(
+        "{}
+@group(3) @binding(2) var<uniform> mesh_index: u32;
+
+{}
+{}
+fn skin_model_vertex(input: SkinInput) -> SkinnedGeometry {{ return {}(input); }}
+",
+        model_palettes_wgsl(storage_palettes),
+        morph_targets,
+        SKINNING_WGSL,
+        skin_function
+    )
+}
+
+/// A vertex written by the skinning pass, in the model's space. Each field is a vec4 to match the
+/// layout of the storage array the pass writes, w is unused.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinnedVertex {
+    pub position: Vec4,
+    pub normal: Vec4,
+    pub tangent: Vec4,
+    pub bi_tangent: Vec4,
+    pub uv: Vec4,
+}
+
+impl SkinnedVertex {
+    /// The same shader locations as `ModelVertex`, without the bone ids and weights.
+    pub fn vertex_description() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SkinnedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                // position
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                // normal
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_NORMAL as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                // tex coords
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_TEXCOORDS as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // tangent
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_TANGENT as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                // bitangent
+                wgpu::VertexAttribute {
+                    offset: OFFSET_OF_BITANGENT as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+/// The output of the skinning pass for one mesh.
+#[derive(Debug)]
+pub struct SkinnedMesh {
+    pub mesh_id: i32,
+    /// The skinned vertices, drawn with the mesh's index buffer and `SkinnedVertex::vertex_description`.
+    pub vertex_buffer: Buffer,
+    pub vertex_count: u32,
+    pub bind_group: BindGroup,
+}
+
+/// Skins every mesh of a model once per frame into its own vertex buffer, so the passes
+/// that draw the model, such as shadows and the main pass, treat it as static geometry.
+#[derive(Debug)]
+pub struct ComputeSkinning {
+    pub pipeline: ComputePipeline,
+    pub skinned_meshes: Vec<SkinnedMesh>,
+}
+
+impl ComputeSkinning {
+    /// The palettes have to be storage buffers, see `GpuContext::supports_compute_skinning`.
+    pub fn new(
+        context: &mut GpuContext,
+        meshes: &[ModelMesh],
+        final_nodes: &Buffer,
+        final_bones: &Buffer,
+        final_bone_dual_quats: &Buffer,
+        skinning_method: SkinningMethod,
+    ) -> ComputeSkinning {
+        if !context.bind_layout_cache.contains_key(SKINNING_BIND_GROUP_LAYOUT) {
+            let layout = create_skinning_bind_group_layout(context);
+            context
+                .bind_layout_cache
+                .insert(String::from(SKINNING_BIND_GROUP_LAYOUT), layout.into());
+        }
+
+        let bind_group_layout = context.bind_layout_cache.get(SKINNING_BIND_GROUP_LAYOUT).unwrap();
+
+        let source = format!("{SKINNING_WGSL}\n{MORPH_TARGETS_WGSL}\n{COMPUTE_SKINNING_WGSL}");
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("compute skinning shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("compute skinning pipeline layout"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("compute skinning pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: match skinning_method {
+                SkinningMethod::Linear => "main",
+                SkinningMethod::DualQuaternion => "main_dual_quaternion",
+            },
+        });
+
+        // a storage binding can't be empty, meshes without vertices have nothing to skin
+        let skinned_meshes = meshes
+            .iter()
+            .filter(|mesh| mesh.num_vertices > 0)
+            .map(|mesh| {
+                let vertex_count = mesh.num_vertices;
+
+                let vertex_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("skinned vertex buffer"),
+                    size: (vertex_count as usize * mem::size_of::<SkinnedVertex>()) as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+
+                let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: final_nodes.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: final_bones.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: mesh.vertex_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: mesh.morph_deltas_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: mesh.morph_weights_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: mesh.mesh_index_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 6,
+                            resource: vertex_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 7,
+                            resource: final_bone_dual_quats.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("skinning bind group"),
+                });
+
+                SkinnedMesh {
+                    mesh_id: mesh.id,
+                    vertex_buffer,
+                    vertex_count,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        ComputeSkinning { pipeline, skinned_meshes }
+    }
+
+    /// Records the pass skinning every mesh. The palettes and morph weights must already be written.
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("compute skinning pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipeline);
+
+        for skinned_mesh in self.skinned_meshes.iter() {
+            compute_pass.set_bind_group(0, &skinned_mesh.bind_group, &[]);
+            compute_pass.dispatch_workgroups(skinned_mesh.vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    pub fn get_skinned_mesh(&self, mesh_id: i32) -> Option<&SkinnedMesh> {
+        self.skinned_meshes.iter().find(|skinned_mesh| skinned_mesh.mesh_id == mesh_id)
+    }
+}
+
+fn create_skinning_bind_group_layout(context: &GpuContext) -> BindGroupLayout {
+    let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    let read_only = wgpu::BufferBindingType::Storage { read_only: true };
+
+    context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            // 0: final_nodes_matrices
+            buffer_entry(0, read_only),
+            // 1: final_bones_matrices
+            buffer_entry(1, read_only),
+            // 2: the mesh's vertices
+            buffer_entry(2, read_only),
+            // 3: morph deltas
+            buffer_entry(3, read_only),
+            // 4: morph weights
+            buffer_entry(4, read_only),
+            // 5: mesh index
+            buffer_entry(5, wgpu::BufferBindingType::Uniform),
+            // 6: skinned vertices
+            buffer_entry(6, wgpu::BufferBindingType::Storage { read_only: false }),
+            // 7: final_bone_dual_quats
+            buffer_entry(7, read_only),
+        ],
+        label: Some("skinning bind group layout"),
+    })
+}
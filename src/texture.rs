@@ -80,11 +80,9 @@ pub fn get_texture(context: &GpuContext, file_path: impl Into<PathBuf>) -> Resul
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 pub fn create_depth_texture(context: &GpuContext) -> Texture {
-    let size = context.window.inner_size();
-
     let size = wgpu::Extent3d {
-        width: size.width,
-        height: size.height,
+        width: context.size.width,
+        height: context.size.height,
         depth_or_array_layers: 1,
     };
     let desc = wgpu::TextureDescriptor {
@@ -1,3 +1,4 @@
+#[cfg(feature = "russimp")]
 use russimp::sys::aiTextureType;
 use std::fmt::{Display, Formatter};
 
@@ -41,6 +42,7 @@ pub enum TextureType {
 }
 
 impl TextureType {
+    #[cfg(feature = "russimp")]
     pub fn convert_from(r_texture_type: &russimp::material::TextureType) -> Self {
         match r_texture_type {
             russimp::material::TextureType::None => TextureType::None,
@@ -100,6 +102,7 @@ impl Display for TextureType {
     }
 }
 
+#[cfg(feature = "russimp")]
 impl From<TextureType> for aiTextureType {
     fn from(value: TextureType) -> Self {
         match value {
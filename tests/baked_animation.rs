@@ -0,0 +1,193 @@
+#[allow(dead_code)]
+mod common;
+
+use glam::{Mat4, Quat, Vec3};
+use spark_gap::animator::Animator;
+use spark_gap::baked_animation::{
+    baked_animation_wgsl, BakedAnimationData, BakedAnimations, BakedInstance, BAKED_ANIMATION_BIND_GROUP_LAYOUT,
+};
+use spark_gap::buffers::create_vertex_buffer_init;
+use spark_gap::hash_map::HashMap;
+use spark_gap::model_animation::{BoneData, ModelAnimation, NodeData};
+use spark_gap::model_mesh::ModelVertex;
+use spark_gap::node_animation::{KeyPosition, KeyRotation, KeyScale, NodeAnimation};
+use spark_gap::transform::Transform;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const SHADER: &str = "
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(5) bone_ids: vec4<i32>,
+    @location(6) weights: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(7) model_0: vec4<f32>,
+    @location(8) model_1: vec4<f32>,
+    @location(9) model_2: vec4<f32>,
+    @location(10) model_3: vec4<f32>,
+    @location(11) clip_index: u32,
+    @location(12) time: f32,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let skin = baked_skin_matrix(vertex.bone_ids, vertex.weights, 0u, instance.clip_index, instance.time);
+    return model * skin * vec4<f32>(vertex.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+fn quad_vertex(x: f32, y: f32) -> ModelVertex {
+    let mut vertex = ModelVertex::new();
+    vertex.position = Vec3::new(x, y, 0.5);
+    vertex.set_bone_data(0, 1.0);
+    vertex
+}
+
+// a bone sliding along x from -0.5 to 0.5 over a second
+fn sliding_animator() -> Animator {
+    let root_node = NodeData {
+        name: Rc::from("root"),
+        transform: Transform::IDENTITY,
+        children: vec![NodeData {
+            name: Rc::from("bone"),
+            transform: Transform::IDENTITY,
+            children: vec![],
+            meshes: Rc::new(vec![0]),
+        }],
+        meshes: Rc::new(vec![]),
+    };
+
+    let node_animation = NodeAnimation::from_keys(
+        Rc::from("bone"),
+        vec![
+            KeyPosition::new(Vec3::new(-0.5, 0.0, 0.0), 0.0),
+            KeyPosition::new(Vec3::new(0.5, 0.0, 0.0), 1000.0),
+        ],
+        vec![KeyRotation::new(Quat::IDENTITY, 0.0)],
+        vec![KeyScale::new(Vec3::ONE, 0.0)],
+    );
+    let animation = ModelAnimation {
+        name: Rc::from("slide"),
+        duration: 1000.0,
+        ticks_per_second: 1000.0,
+        node_animations: RefCell::new(vec![node_animation]),
+    };
+
+    let mut bone_data_map = HashMap::new();
+    bone_data_map.insert("bone".to_string(), BoneData::new("bone", 0, Mat4::IDENTITY));
+
+    Animator::from_node_data(root_node, vec![animation], RefCell::new(bone_data_map))
+}
+
+#[test]
+fn test_baked_instances() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    // two frames a second, so the second instance falls halfway between baked frames
+    let data = BakedAnimationData::bake(&sliding_animator(), 2.0);
+    let baked_animations = BakedAnimations::new(&mut context, data).unwrap();
+    let clip_index = baked_animations.clip_index("slide").unwrap();
+
+    let vertices = [
+        quad_vertex(-0.1, -0.1),
+        quad_vertex(0.1, -0.1),
+        quad_vertex(0.1, 0.1),
+        quad_vertex(-0.1, -0.1),
+        quad_vertex(0.1, 0.1),
+        quad_vertex(-0.1, 0.1),
+    ];
+    let vertex_buffer = create_vertex_buffer_init(&context, &vertices, "baked quad vertices");
+
+    // at x = -0.5 in the top half and at x = 0.25 in the bottom half
+    let instances = [
+        BakedInstance::new(Mat4::from_translation(Vec3::new(0.0, 0.5, 0.0)), clip_index, 0.0),
+        BakedInstance::new(Mat4::from_translation(Vec3::new(0.0, -0.5, 0.0)), clip_index, 0.75),
+    ];
+    let instance_buffer = create_vertex_buffer_init(&context, &instances, "baked instances");
+
+    let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("baked animation shader"),
+        source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", baked_animation_wgsl(0), SHADER).into()),
+    });
+
+    let bind_group_layout = context.bind_layout_cache.get(BAKED_ANIMATION_BIND_GROUP_LAYOUT).unwrap();
+
+    let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("baked animation pipeline layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("baked animation pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[ModelVertex::vertex_description(), BakedInstance::vertex_description()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(context.config.format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let frame = context.get_current_frame();
+    let view = frame.create_view();
+
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("baked animation pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &baked_animations.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..instances.len() as u32);
+    }
+    context.queue.submit(Some(encoder.finish()));
+    frame.present();
+
+    let image = context.capture_offscreen_target().unwrap();
+    let is_drawn = |x: f32, y: f32| {
+        let pixel_x = ((x + 1.0) * 0.5 * common::WIDTH as f32) as u32;
+        let pixel_y = ((1.0 - y) * 0.5 * common::HEIGHT as f32) as u32;
+        image.get_pixel(pixel_x, pixel_y)[0] > 128
+    };
+
+    assert!(is_drawn(-0.5, 0.5));
+    assert!(!is_drawn(0.25, 0.5));
+    assert!(is_drawn(0.25, -0.5));
+    assert!(!is_drawn(0.0, -0.5));
+    assert!(!is_drawn(0.5, -0.5));
+}
@@ -0,0 +1,65 @@
+use spark_gap::golden_image::{check_golden, CompareConfig};
+use spark_gap::gpu_context::GpuContext;
+use std::path::PathBuf;
+
+pub const WIDTH: u32 = 256;
+pub const HEIGHT: u32 = 256;
+
+/// Set to skip the gpu tests on machines without a usable adapter instead of failing them.
+pub const SKIP_GPU_TESTS_ENV_VAR: &str = "SPARK_GAP_SKIP_GPU_TESTS";
+
+/// Creates a headless context, preferring the fallback (software) adapter so the
+/// reference images don't depend on the GPU. Returns None when there is no adapter
+/// and `SPARK_GAP_SKIP_GPU_TESTS` is set, panics otherwise.
+pub fn headless_context() -> Option<GpuContext> {
+    let Some(force_fallback_adapter) = find_adapter() else {
+        skip_gpu_test("no adapter available");
+        return None;
+    };
+    Some(pollster::block_on(GpuContext::new_headless(WIDTH, HEIGHT, force_fallback_adapter)))
+}
+
+/// Reports a gpu test that can't run on this machine, so that a missing adapter or feature
+/// doesn't pass the test silently.
+pub fn skip_gpu_test(reason: &str) {
+    if std::env::var_os(SKIP_GPU_TESTS_ENV_VAR).is_none() {
+        panic!("{reason}, set {SKIP_GPU_TESTS_ENV_VAR}=1 to skip the gpu tests");
+    }
+    println!("skipping gpu test, {reason}");
+}
+
+// The probe instance has to be dropped before the context creates its own, on GL dropping an
+// instance terminates the display shared with any other instance.
+fn find_adapter() -> Option<bool> {
+    let instance = wgpu::Instance::default();
+
+    [true, false].into_iter().find(|force_fallback_adapter| {
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: *force_fallback_adapter,
+        }))
+        .is_some()
+    })
+}
+
+pub fn assert_matches_golden(context: &GpuContext, name: &str) {
+    let actual = context.capture_offscreen_target().unwrap();
+
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+
+    let output_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+
+    // software and hardware rasterizers differ a little along edges
+    let config = CompareConfig {
+        channel_tolerance: 4,
+        perceptual_threshold: 0.1,
+        max_different_ratio: 0.005,
+    };
+
+    if let Err(e) = check_golden(&actual, &reference_path, &output_dir, &config) {
+        panic!("{}", e);
+    }
+}
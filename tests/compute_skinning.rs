@@ -0,0 +1,195 @@
+#[allow(dead_code)]
+mod common;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+use spark_gap::dual_quat::DualQuat;
+use spark_gap::gpu_context::GpuContext;
+use spark_gap::model_mesh::{ModelMesh, ModelVertex, MorphTargetDelta, MorphTargets};
+use spark_gap::skinning::{ComputeSkinning, SkinnedVertex, SkinningMethod};
+use spark_gap::transform::Transform;
+use std::sync::mpsc;
+use wgpu::util::DeviceExt;
+
+fn palette_buffer<T: bytemuck::Pod>(context: &GpuContext, palette: &[T]) -> wgpu::Buffer {
+    context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("test palette"),
+        contents: bytemuck::cast_slice(palette),
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+fn read_skinned_vertices(context: &GpuContext, buffer: &wgpu::Buffer) -> Vec<SkinnedVertex> {
+    let staging_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("skinned vertices staging buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("read back") });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+    context.queue.submit(Some(encoder.finish()));
+
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    context.device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap().unwrap();
+
+    let vertices = bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+    staging_buffer.unmap();
+    vertices
+}
+
+#[test]
+fn test_compute_skinning() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    if !context.supports_compute_skinning() {
+        common::skip_gpu_test("the adapter can't run the compute skinning pass");
+        return;
+    }
+
+    // the first vertex follows bone 0, the second has no bones and follows the mesh's node
+    let mut skinned = ModelVertex::new();
+    skinned.position = Vec3::new(1.0, 0.0, 0.0);
+    skinned.normal = Vec3::X;
+    skinned.tangent = Vec3::Y;
+    skinned.bi_tangent = Vec3::Z;
+    skinned.set_bone_data(0, 1.0);
+
+    let mut unskinned = ModelVertex::new();
+    unskinned.position = Vec3::new(0.0, 1.0, 0.0);
+    unskinned.normal = Vec3::Y;
+
+    // moves the first vertex along x at full weight
+    let morph_targets = MorphTargets {
+        target_count: 1,
+        deltas: vec![
+            MorphTargetDelta {
+                position: Vec4::new(1.0, 0.0, 0.0, 0.0),
+                ..Default::default()
+            },
+            MorphTargetDelta::default(),
+        ],
+        default_weights: vec![0.5],
+    };
+
+    let mesh = ModelMesh::new(
+        &mut context,
+        0,
+        "mesh",
+        vec![skinned, unskinned],
+        vec![0, 1, 1],
+        vec![],
+        morph_targets,
+    );
+
+    let bone = Mat4::from_rotation_translation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2), Vec3::new(1.0, 2.0, 3.0));
+    let node = Mat4::from_translation(Vec3::new(0.0, 5.0, 0.0));
+    let final_bones = palette_buffer(&context, &[bone]);
+    let final_nodes = palette_buffer(&context, &[node]);
+    let final_bone_dual_quats = palette_buffer(&context, &[DualQuat::IDENTITY]);
+
+    let meshes = vec![mesh];
+    let compute_skinning = ComputeSkinning::new(
+        &mut context,
+        &meshes,
+        &final_nodes,
+        &final_bones,
+        &final_bone_dual_quats,
+        SkinningMethod::Linear,
+    );
+
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("skinning") });
+    compute_skinning.dispatch(&mut encoder);
+    context.queue.submit(Some(encoder.finish()));
+
+    let skinned_mesh = compute_skinning.get_skinned_mesh(0).unwrap();
+    let vertices = read_skinned_vertices(&context, &skinned_mesh.vertex_buffer);
+    assert_eq!(vertices.len(), 2);
+
+    // morphed to x = 1.5, then turned to y = 1.5 and moved by the bone
+    let expected = bone.transform_point3(Vec3::new(1.5, 0.0, 0.0));
+    assert!(vertices[0].position.truncate().abs_diff_eq(expected, 1e-5));
+    assert!(vertices[0].normal.truncate().abs_diff_eq(Vec3::Y, 1e-5));
+    assert!(vertices[0].tangent.truncate().abs_diff_eq(-Vec3::X, 1e-5));
+    assert!(vertices[0].bi_tangent.truncate().abs_diff_eq(Vec3::Z, 1e-5));
+
+    assert!(vertices[1].position.truncate().abs_diff_eq(Vec3::new(0.0, 6.0, 0.0), 1e-5));
+    assert!(vertices[1].normal.truncate().abs_diff_eq(Vec3::Y, 1e-5));
+}
+
+#[test]
+fn test_compute_dual_quaternion_skinning() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    if !context.supports_compute_skinning() {
+        common::skip_gpu_test("the adapter can't run the compute skinning pass");
+        return;
+    }
+
+    // halfway between a bone at rest and one twisted almost half a turn
+    let mut vertex = ModelVertex::new();
+    vertex.position = Vec3::new(0.0, 1.0, 0.0);
+    vertex.normal = Vec3::Y;
+    vertex.set_bone_data(0, 0.5);
+    vertex.set_bone_data(1, 0.5);
+
+    let mesh = ModelMesh::new(
+        &mut context,
+        0,
+        "mesh",
+        vec![vertex],
+        vec![0, 0, 0],
+        vec![],
+        MorphTargets::default(),
+    );
+
+    let twist = Transform::from_xyz(0.0, 0.0, 2.0).with_rotation(Quat::from_rotation_x(std::f32::consts::PI * 0.9));
+    let dual_quats = [
+        DualQuat::from_transform(&Transform::from_xyz(0.0, 0.0, 2.0)),
+        DualQuat::from_transform(&twist),
+    ];
+    let bones = [Mat4::from_translation(Vec3::new(0.0, 0.0, 2.0)), twist.compute_matrix()];
+
+    let final_nodes = palette_buffer(&context, &[Mat4::IDENTITY]);
+    let final_bones = palette_buffer(&context, &bones);
+    let final_bone_dual_quats = palette_buffer(&context, &dual_quats);
+
+    let meshes = vec![mesh];
+    let compute_skinning = ComputeSkinning::new(
+        &mut context,
+        &meshes,
+        &final_nodes,
+        &final_bones,
+        &final_bone_dual_quats,
+        SkinningMethod::DualQuaternion,
+    );
+
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("skinning") });
+    compute_skinning.dispatch(&mut encoder);
+    context.queue.submit(Some(encoder.finish()));
+
+    let vertices = read_skinned_vertices(&context, &compute_skinning.get_skinned_mesh(0).unwrap().vertex_buffer);
+
+    // the vertex keeps its distance from the twist axis, the same as the blend on the cpu
+    let expected = DualQuat::blend(&[(dual_quats[0], 0.5), (dual_quats[1], 0.5)])
+        .unwrap()
+        .transform_point(Vec3::new(0.0, 1.0, 0.0));
+    let position = vertices[0].position.truncate();
+    assert!(position.abs_diff_eq(expected, 1e-4));
+    assert!((Vec3::new(0.0, position.y, position.z - 2.0).length() - 1.0).abs() < 1e-4);
+}
@@ -0,0 +1,118 @@
+#[path = "../examples/draw_cube/cube.rs"]
+mod cube;
+
+mod common;
+
+use crate::cube::Cube;
+use glam::{Mat4, Vec3};
+use spark_gap::buffers::{create_buffer_bind_group, create_mat4_buffer_init, create_uniform_bind_group_layout, create_vertex_buffer_init};
+use spark_gap::small_mesh::SmallMesh;
+use spark_gap::texture::{get_texture, get_texture_bind_group, DEPTH_FORMAT};
+use std::f32::consts;
+
+const BACKGROUND_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.1,
+    a: 1.0,
+};
+
+#[test]
+fn test_container_cube() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    let cube = Cube::new();
+    let vertex_buffer = create_vertex_buffer_init(&context, &cube.vertex_data, "container vertex buffer");
+
+    // same fixed camera as the draw_cube example
+    let aspect_ratio = context.size.width as f32 / context.size.height as f32;
+    let projection = Mat4::perspective_rh(consts::FRAC_PI_4, aspect_ratio, 1.0, 10.0);
+    let view = Mat4::look_at_rh(Vec3::new(1.5, -5.0, 3.0), Vec3::ZERO, Vec3::Z);
+    let transform_buffer = create_mat4_buffer_init(&mut context, &(projection * view), "container transform");
+
+    let transform_layout = create_uniform_bind_group_layout(&context, "container transform layout");
+    let transform_bind_group = create_buffer_bind_group(&context, &transform_layout, &transform_buffer, "container transform");
+
+    let texture_path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/draw_cube/container2.png");
+    let texture = get_texture(&context, texture_path).unwrap();
+    let (texture_layout, texture_bind_group) = get_texture_bind_group(&context, &texture);
+
+    let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("draw_cube shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../examples/draw_cube/shader.wgsl").into()),
+    });
+
+    let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("container pipeline layout"),
+        bind_group_layouts: &[&transform_layout, &texture_layout],
+        push_constant_ranges: &[],
+    });
+
+    let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("container pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[SmallMesh::vertex_description()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(context.config.format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let frame = context.get_current_frame();
+    let view = frame.create_view();
+    let depth_view = &context.offscreen_target.as_ref().unwrap().depth_texture.view;
+
+    let mut encoder = context
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("container pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(BACKGROUND_COLOR),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_bind_group(0, &transform_bind_group, &[]);
+        render_pass.set_bind_group(1, &texture_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..cube.num_elements, 0..1);
+    }
+    context.queue.submit(Some(encoder.finish()));
+    frame.present();
+
+    common::assert_matches_golden(&context, "container_cube");
+}
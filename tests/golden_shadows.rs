@@ -0,0 +1,60 @@
+#[path = "../examples/shadows/cube.rs"]
+mod cube;
+#[path = "../examples/shadows/debug_shadow.rs"]
+mod debug_shadow;
+#[path = "../examples/shadows/entities.rs"]
+mod entities;
+#[path = "../examples/shadows/forward_pass.rs"]
+mod forward_pass;
+#[path = "../examples/shadows/lights.rs"]
+mod lights;
+#[path = "../examples/shadows/shadow_pass.rs"]
+mod shadow_pass;
+#[path = "../examples/shadows/world.rs"]
+mod world;
+
+mod common;
+
+use crate::world::World;
+
+#[test]
+fn test_shadows_forward_pass() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    let mut world = World::new(&mut context);
+
+    // each render rotates the cubes by a fixed step, so the frame count is the clock
+    for _ in 0..10 {
+        world.render(&context);
+    }
+
+    common::assert_matches_golden(&context, "shadows_forward_pass");
+}
+
+#[test]
+fn test_shadows_light_view() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    let mut world = World::new(&mut context);
+    world.camera_position = 1;
+    world.render(&context);
+
+    common::assert_matches_golden(&context, "shadows_light_view");
+}
+
+#[test]
+fn test_shadows_shadow_map() {
+    let Some(mut context) = common::headless_context() else {
+        return;
+    };
+
+    let mut world = World::new(&mut context);
+    world.show_shadows = true;
+    world.render(&context);
+
+    common::assert_matches_golden(&context, "shadows_shadow_map");
+}
@@ -0,0 +1,89 @@
+#[allow(dead_code)]
+mod common;
+
+use spark_gap::model_builder::ModelBuilder;
+use spark_gap::model_mesh::{create_mesh_bind_group_layout, ModelVertex};
+use spark_gap::skinning::{skinning_wgsl, SkinningMethod};
+
+const SHADER: &str = "
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bi_tangent: vec3<f32>,
+    @location(5) bone_ids: vec4<i32>,
+    @location(6) weights: vec4<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput) -> @builtin(position) vec4<f32> {
+    var input: SkinInput;
+    input.position = vertex.position;
+    input.normal = vertex.normal;
+    input.tangent = vertex.tangent;
+    input.bitangent = vertex.bi_tangent;
+    input.bone_ids = vertex.bone_ids;
+    input.weights = vertex.weights;
+    input.vertex_index = vertex.vertex_index;
+
+    return model_transform * skin_model_vertex(input).position;
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+// the uniform palettes and the mesh group without morph targets, for devices without storage buffers in vertex shaders
+#[test]
+fn test_skinning_without_vertex_storage() {
+    let Some(context) = common::headless_context() else {
+        return;
+    };
+
+    context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let empty_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[],
+        label: Some("empty bind group layout"),
+    });
+    let model_layout = ModelBuilder::create_model_bind_group_layout(&context, false);
+    let mesh_layout = create_mesh_bind_group_layout(&context, false);
+
+    for skinning_method in [SkinningMethod::Linear, SkinningMethod::DualQuaternion] {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("fallback skinning shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", skinning_wgsl(false, skinning_method), SHADER).into()),
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("fallback skinning pipeline layout"),
+            bind_group_layouts: &[&empty_layout, &model_layout, &empty_layout, &mesh_layout],
+            push_constant_ranges: &[],
+        });
+
+        let _pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("fallback skinning pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::vertex_description()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(context.config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+    }
+
+    let error = pollster::block_on(context.device.pop_error_scope());
+    assert!(error.is_none(), "{}", error.unwrap());
+}